@@ -0,0 +1,109 @@
+use crate::errors::UpstreamError;
+use crate::Region;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tokio::sync::oneshot;
+
+pub type BatchResult = Result<Option<Value>, UpstreamError>;
+
+// WG endpoint (ex "clans/accountinfo"), application_id and region, lookups
+// only merge when all three match. Each app ID has its own rate limit, a
+// lookup mustn't go out on another one's
+pub type BatchKey = (&'static str, String, Region);
+
+pub struct PendingBatch {
+    pub id: u64,
+    pub waiters: Vec<(u32, oneshot::Sender<BatchResult>)>,
+}
+
+impl PendingBatch {
+    pub fn account_ids(&self) -> Vec<u32> {
+        let mut ids: Vec<u32> = self.waiters.iter().map(|(id, _)| *id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    pub fn resolve(self, data: HashMap<String, Option<Value>>) {
+        for (account_id, sender) in self.waiters {
+            let value = data.get(&account_id.to_string()).cloned().flatten();
+            let _ = sender.send(Ok(value));
+        }
+    }
+
+    pub fn fail(self, error: UpstreamError) {
+        for (_, sender) in self.waiters {
+            let _ = sender.send(Err(error.clone()));
+        }
+    }
+}
+
+pub enum Enqueued {
+    // first lookup of a new batch, flush it once the window is over
+    First(u64),
+    // batch hit max size, flush right away
+    Full(PendingBatch),
+    Waiting,
+}
+
+/// Collects concurrent `account_id` lookups against the same endpoint so
+/// they go out as one comma separated request.
+pub struct AccountBatcher {
+    max_batch_size: usize,
+    next_id: Mutex<u64>,
+    pending: Mutex<HashMap<BatchKey, PendingBatch>>,
+}
+
+impl AccountBatcher {
+    pub fn new(max_batch_size: usize) -> AccountBatcher {
+        AccountBatcher {
+            max_batch_size: max_batch_size.max(1),
+            next_id: Mutex::new(0),
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn enqueue(
+        &self,
+        key: BatchKey,
+        account_id: u32,
+        sender: oneshot::Sender<BatchResult>,
+    ) -> Enqueued {
+        let mut pending = self.pending.lock().unwrap();
+
+        if let Some(batch) = pending.get_mut(&key) {
+            batch.waiters.push((account_id, sender));
+            if batch.account_ids().len() >= self.max_batch_size {
+                return Enqueued::Full(pending.remove(&key).unwrap());
+            }
+            return Enqueued::Waiting;
+        }
+
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
+        let batch = PendingBatch {
+            id,
+            waiters: vec![(account_id, sender)],
+        };
+
+        if self.max_batch_size == 1 {
+            return Enqueued::Full(batch);
+        }
+        pending.insert(key, batch);
+        Enqueued::First(id)
+    }
+
+    /// Takes the batch out if it's still the one the flush was scheduled for,
+    /// it may already have gone out early after filling up.
+    pub fn take(&self, key: &BatchKey, id: u64) -> Option<PendingBatch> {
+        let mut pending = self.pending.lock().unwrap();
+        match pending.get(key) {
+            Some(batch) if batch.id == id => pending.remove(key),
+            _ => None,
+        }
+    }
+}
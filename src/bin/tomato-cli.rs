@@ -0,0 +1,293 @@
+//! Runs the /stats, /compare, /marks and /clanstats lookups from a terminal and prints
+//! the embeds the bot would send, as text or JSON. Uses the same config as
+//! the bot, but never touches its store.
+
+use clap::{Parser, Subcommand};
+use poise::serenity_prelude::CreateEmbed;
+use std::process::ExitCode;
+use tokio::join;
+use tomatobot::clans::generate_clan_embed;
+use tomatobot::client::{TomatoClient, UpstreamClient, WargamingClient};
+use tomatobot::config::Config;
+use tomatobot::emoji::Emojis;
+use tomatobot::metrics::Metrics;
+use tomatobot::player_stats::compare::generate_compare_embed;
+use tomatobot::player_stats::player::{
+    generate_main_stat_embed, generate_period_embed, Period, Player, PlayerData,
+};
+use tomatobot::scheduler::SchedulerStatus;
+use tomatobot::service::{PlayerLookup, TankCaches, TankFilter, TankLookup};
+use tomatobot::tanks::{
+    fetch_recent_tank_stats, fetch_tank_data, fetch_tank_economics, generate_mark_embed,
+};
+use tomatobot::{embed, Error, Region, StatsService};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser)]
+#[command(name = "tomato-cli", about = "TomatoBot's commands without Discord")]
+struct Cli {
+    /// Print each embed as one line of JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Player stats, like /stats. Takes several names for bulk lookups
+    Stats {
+        #[arg(required = true)]
+        users: Vec<String>,
+        /// NA, EU or ASIA, searched in every region when left out
+        #[arg(long)]
+        region: Option<Region>,
+        /// R24HR, R3DAYS, R7DAYS, R30DAYS, R60DAYS, R1000BATTLES or R100BATTLES
+        #[arg(long)]
+        period: Option<Period>,
+    },
+    /// MoE and mastery requirements, like /marks
+    Marks {
+        tank: String,
+        /// NA, EU or ASIA
+        #[arg(long, default_value = "NA")]
+        region: Region,
+    },
+    /// Two players side by side, like /compare
+    Compare {
+        player_a: String,
+        player_b: String,
+        /// NA, EU or ASIA, searched in every region when left out
+        #[arg(long)]
+        region: Option<Region>,
+        /// R24HR, R3DAYS, R7DAYS, R30DAYS, R60DAYS, R1000BATTLES or R100BATTLES
+        #[arg(long, default_value = "R30DAYS")]
+        period: Period,
+    },
+    /// Clan stats, like /clanstats
+    Clanstats {
+        clan: String,
+        /// NA, EU or ASIA
+        #[arg(long)]
+        region: Region,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    // logs go to stderr so --json output can be piped
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| "warn".into()))
+        .with_writer(std::io::stderr)
+        .init();
+
+    match run(cli).await {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(2),
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Ok(false) when some lookup came back empty, the rest still print.
+async fn run(cli: Cli) -> Result<bool, Error> {
+    let config = Config::load()?;
+    let http = UpstreamClient::new(&config.http, Metrics::new())?;
+    let tomato = TomatoClient::new(http.clone(), &config);
+    let wargaming = WargamingClient::new(http, &config);
+    let print = |embed: &CreateEmbed| match cli.json {
+        true => println!("{}", embed::to_json(embed)),
+        false => println!("{}\n", embed::to_text(embed)),
+    };
+
+    match &cli.command {
+        Command::Stats {
+            users,
+            region,
+            period,
+        } => {
+            let service = StatsService::new(
+                tomato,
+                wargaming,
+                TankCaches::default(),
+                SchedulerStatus::default(),
+            );
+            let mut all_found = true;
+            for user in users {
+                match stats(&service, user, *region, *period).await {
+                    Ok(embed) => print(&embed),
+                    Err(message) => {
+                        eprintln!("{}: {}", user, message);
+                        all_found = false;
+                    }
+                }
+            }
+            Ok(all_found)
+        }
+        Command::Marks { tank, region } => {
+            let caches = load_caches(&tomato, *region).await?;
+            let service = StatsService::new(tomato, wargaming, caches, SchedulerStatus::default());
+            let TankLookup::Found(marks) = service
+                .find_tank(*region, tank, &TankFilter::default())
+                .await
+            else {
+                return Err(format!("No tanks found for {}", region.name()).into());
+            };
+            let embed = generate_mark_embed(
+                &marks.tank,
+                region,
+                marks.economics.as_ref(),
+                marks.recent_stats.as_ref(),
+                &marks.ages,
+                &Emojis::from_config(&config.emojis),
+            )
+            .await;
+            print(&embed);
+            // the embed fills in what's missing, still tell scripts about it
+            Ok(marks.economics.is_some() && marks.recent_stats.is_some())
+        }
+        Command::Compare {
+            player_a,
+            player_b,
+            region,
+            period,
+        } => {
+            let service = StatsService::new(
+                tomato,
+                wargaming,
+                TankCaches::default(),
+                SchedulerStatus::default(),
+            );
+            let (data_a, data_b) = join!(
+                compared_player(&service, player_a, *region),
+                compared_player(&service, player_b, *region)
+            );
+            let mut players = Vec::with_capacity(2);
+            for (user, data) in [(player_a, data_a), (player_b, data_b)] {
+                let message = match data {
+                    Ok(data) => match data.missing_stats_message(None) {
+                        Some(message) => message,
+                        None => {
+                            players.push(data);
+                            continue;
+                        }
+                    },
+                    Err(message) => message,
+                };
+                eprintln!("{}: {}", user, message);
+            }
+            let [data_a, data_b] = players.as_slice() else {
+                return Ok(false);
+            };
+            print(&generate_compare_embed(data_a, data_b, *period).await?);
+            Ok(true)
+        }
+        Command::Clanstats { clan, region } => {
+            let service = StatsService::new(
+                tomato,
+                wargaming,
+                TankCaches::default(),
+                SchedulerStatus::default(),
+            );
+            let Some(clan_id) = service.find_clan(*region, clan).await? else {
+                eprintln!("{}: Couldn't find a clan with that name", clan);
+                return Ok(false);
+            };
+            print(&generate_clan_embed(&service.clan_data(*region, clan_id).await?).await);
+            Ok(true)
+        }
+    }
+}
+
+/// The player, or why there isn't exactly one.
+async fn find_player(
+    service: &StatsService,
+    user: &str,
+    region: Option<Region>,
+) -> Result<(Region, Player), String> {
+    match service.find_player(user, region).await {
+        Ok(PlayerLookup::Found(region, player)) => Ok((region, player)),
+        Ok(PlayerLookup::Ambiguous {
+            candidates,
+            unchecked,
+        }) => {
+            let regions: Vec<&str> = candidates.iter().map(|c| c.region.name()).collect();
+            let mut message = format!("Found in {}", regions.join(", "));
+            if !unchecked.is_empty() {
+                let unchecked: Vec<&str> = unchecked.iter().map(|r| r.name()).collect();
+                message.push_str(&format!(", couldn't check {}", unchecked.join(", ")));
+            }
+            message.push_str(", pick one with --region");
+            Err(message)
+        }
+        Ok(PlayerLookup::NotFound) => Err(String::from("No player found with that name")),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+// /compare doesn't show clans or a first embed, only the uncached stats
+async fn compared_player(
+    service: &StatsService,
+    user: &str,
+    region: Option<Region>,
+) -> Result<PlayerData, String> {
+    let (region, player) = find_player(service, user, region).await?;
+    Ok(service.player_stats(region, player).await)
+}
+
+/// Both passes like /stats, printing only the final embed.
+async fn stats(
+    service: &StatsService,
+    user: &str,
+    region: Option<Region>,
+    period: Option<Period>,
+) -> Result<CreateEmbed, String> {
+    let (region, player) = find_player(service, user, region).await?;
+    let mut data = service.player_data(region, player).await;
+    service.refresh_player_data(&mut data).await;
+    if let Some(message) = data.missing_stats_message(period) {
+        return Err(message);
+    }
+    match period {
+        Some(period) => generate_period_embed(&data, period)
+            .await
+            .map_err(|e| e.to_string()),
+        None => generate_main_stat_embed(&data)
+            .await
+            .map_err(|e| e.to_string()),
+    }
+}
+
+/// The bot keeps these fresh in the background, here they're fetched for
+/// the one region on every run.
+async fn load_caches(tomato: &TomatoClient, region: Region) -> Result<TankCaches, Error> {
+    let (tanks, economics, recent_stats) = join!(
+        fetch_tank_data(tomato, &region),
+        fetch_tank_economics(tomato),
+        fetch_recent_tank_stats(tomato, &region),
+    );
+
+    let caches = TankCaches::default();
+    caches.tank_data.lock().await.insert(region, tanks?);
+    // the embed shows placeholders without these, finding the tank still works
+    match economics {
+        Ok(economics) => *caches.tank_economics.lock().await = economics,
+        Err(e) => tracing::warn!("{}", e),
+    }
+    match recent_stats {
+        Ok(recent_stats) => {
+            caches
+                .recent_tank_stats
+                .lock()
+                .await
+                .insert(region, recent_stats);
+        }
+        Err(e) => tracing::warn!("{}", e),
+    }
+    Ok(caches)
+}
@@ -0,0 +1,219 @@
+use std::collections::HashMap;
+
+use crate::client::{TomatoClient, WargamingClient};
+use crate::errors::UpstreamError;
+use crate::Region;
+use poise::serenity_prelude::CreateEmbed;
+use serde::Deserialize;
+
+use crate::player_stats::player::Emblems;
+
+#[derive(Deserialize, Default)]
+pub struct ClanId {
+    clan_id: u32,
+}
+
+#[derive(Deserialize, Default, Clone)]
+pub struct TomatoClan {
+    pub name: String,
+    pub tag: String,
+    pub color: String,
+    motto: String,
+    pub emblems: Emblems,
+    #[serde(rename = "overallWN8")]
+    overall_wn8: f32,
+    #[serde(rename = "overallWinrate")]
+    overall_winrate: f32,
+    #[serde(rename = "recentWN8")]
+    recent_wn8: f32,
+    #[serde(rename = "recentWinrate")]
+    recent_winrate: f32,
+    members_count: u32,
+}
+
+pub type GlobalData = HashMap<String, Option<GlobalClanData>>;
+
+#[derive(Deserialize, Clone, Default)]
+pub struct GlobalClanData {
+    statistics: GlobalStatistics,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct GlobalStatistics {
+    battles_10_level: u32,
+    wins_10_level: u32,
+    provinces_count: u32,
+}
+
+pub type RatingData = HashMap<String, Option<RatingClanData>>;
+
+#[derive(Deserialize, Clone, Default)]
+pub struct RatingClanData {
+    efficiency: Value,
+    battles_count_avg_daily: Value,
+    global_rating_weighted_avg: Value,
+    fb_elo_rating_10: Value,
+    fb_elo_rating_8: Value,
+    fb_elo_rating_6: Value,
+    gm_elo_rating_10: Value,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct Value {
+    value: f32,
+}
+
+#[derive(Clone)]
+pub struct ClanData {
+    pub rating: Option<RatingClanData>,
+    pub global: Option<GlobalClanData>,
+    pub tomato: TomatoClan,
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn fetch_global_map(
+    client: &WargamingClient,
+    region: Region,
+    clan_id: u32,
+) -> Result<Option<GlobalClanData>, UpstreamError> {
+    let global_map_url = format!(
+        "{}/wot/globalmap/claninfo/?application_id={}&clan_id={}",
+        client.host(&region),
+        client.clan_application_id(),
+        clan_id
+    );
+
+    let response = client
+        .get_json::<GlobalData>(&global_map_url, region)
+        .await?;
+
+    Ok(response.get(&clan_id.to_string()).cloned().flatten())
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn fetch_clan_rating(
+    client: &WargamingClient,
+    region: Region,
+    clan_id: u32,
+) -> Result<Option<RatingClanData>, UpstreamError> {
+    let rating_url = format!(
+        "{}/wot/clanratings/clans/?application_id={}&clan_id={}",
+        client.host(&region),
+        client.clan_application_id(),
+        clan_id
+    );
+
+    let parsed_response = client
+        .get_json::<RatingData>(&rating_url, region)
+        .await?;
+
+    Ok(parsed_response.get(&clan_id.to_string()).cloned().flatten())
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn fetch_tomato_clan(
+    client: &TomatoClient,
+    region: Region,
+    clan_id: u32,
+) -> Result<TomatoClan, UpstreamError> {
+    let tomato_url = format!(
+        "{}/clan/{}/{}",
+        client.legacy_api_base(),
+        region.extension(),
+        clan_id
+    );
+
+    let parsed_response = client
+        .get_json::<TomatoClan>(&tomato_url, Some(region))
+        .await?;
+
+    Ok(parsed_response)
+}
+
+#[tracing::instrument(skip(tomato, wargaming))]
+pub async fn fetch_all_clan(
+    tomato: &TomatoClient,
+    wargaming: &WargamingClient,
+    region: Region,
+    clan_id: u32,
+) -> Result<ClanData, UpstreamError> {
+    let (global_map_result, clan_rating_result, tomato_clan_result) = tokio::join!(
+        fetch_global_map(wargaming, region, clan_id),
+        fetch_clan_rating(wargaming, region, clan_id),
+        fetch_tomato_clan(tomato, region, clan_id)
+    );
+
+    let (rating, global, tomato) = (clan_rating_result?, global_map_result?, tomato_clan_result?);
+
+    Ok(ClanData {
+        rating,
+        global,
+        tomato,
+    })
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn fetch_clan_id(client: &WargamingClient, region: Region, clan: &str) -> Result<Option<u32>, UpstreamError> {
+    let id_url = format!("{}/wot/clans/list/?application_id={}&search={}",
+                         client.host(&region), client.clan_application_id(), clan);
+    let response = client.get_json::<Vec<ClanId>>(&id_url, region).await?;
+
+    Ok(response.first().map(|data| data.clan_id))
+}
+
+pub async fn generate_clan_embed(data: &ClanData) -> CreateEmbed {
+    let tomato = &data.tomato;
+    let rating = data.rating.clone().unwrap_or_default();
+    let global = data.global.clone().unwrap_or_default();
+
+    CreateEmbed::default()
+        .title(format!("[{}] {}", tomato.tag, tomato.name))
+        .thumbnail(&tomato.emblems.x64.portal)
+        .description(&tomato.motto)
+        .field(
+            "Player Stats",
+            format!(
+                "Overall WN8: `{}`\nOverall WR: `{:.1}%`\nRecent WN8: `{}`\nRecent WR: `{:.1}%`",
+                tomato.overall_wn8.round(),
+                tomato.overall_winrate,
+                tomato.recent_wn8.round(),
+                tomato.recent_winrate,
+            ),
+            true,
+        )
+        .field(
+            "General Stats",
+            format!(
+                "Clan Rating: `{}`\nAvg. Daily Battles: `{}`\nAvg. PR: `{}`\nPlayers: `{}`",
+                rating.efficiency.value.round(),
+                rating.battles_count_avg_daily.value.round(),
+                rating.global_rating_weighted_avg.value.round(),
+                tomato.members_count
+            ),
+            true,
+        )
+        .field(
+            "Stronghold Stats",
+            format!(
+                "SH Tier X ELO: `{}`\nSH Tier VIII ELO: `{}`\nSH Tier VI ELO: `{}`",
+                rating.fb_elo_rating_10.value.round(),
+                rating.fb_elo_rating_8.value.round(),
+                rating.fb_elo_rating_6.value.round(),
+            ),
+            true,
+        )
+        .field(
+            "Global Map Stats",
+            format!(
+                "Global Map ELO: `{}`\nGlobal Map WR: `{:.1}%`\nProvinces: `{}`",
+                rating.gm_elo_rating_10.value.round(),
+                (global.statistics.wins_10_level as f32
+                    / global.statistics.battles_10_level as f32)
+                    * 100.0,
+                global.statistics.provinces_count,
+            ),
+            true,
+        )
+        .color(i32::from_str_radix(&tomato.color[1..], 16).unwrap())
+        .to_owned()
+}
@@ -0,0 +1,352 @@
+use crate::batch::{AccountBatcher, BatchKey, BatchResult, Enqueued, PendingBatch};
+use crate::config::{Config, HttpConfig, TomatoConfig, WargamingConfig};
+use crate::errors::{Upstream, UpstreamError, UpstreamErrorKind};
+use crate::metrics::Metrics;
+use crate::ratelimit::RateLimiter;
+use crate::wargaming::WgResponse;
+use crate::Region;
+use rand::Rng;
+use reqwest::{Response, StatusCode, Url};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tracing::{debug, warn, Span};
+
+// past this a retry isn't going to save the command anyway
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// reqwest client shared by every fetch so connections get pooled, with
+/// timeouts and retries for the transient failures Tomato and WG like to throw.
+#[derive(Clone)]
+pub struct UpstreamClient {
+    http: reqwest::Client,
+    max_retries: u32,
+    retry_base_delay: Duration,
+    metrics: Metrics,
+}
+
+impl UpstreamClient {
+    pub fn new(config: &HttpConfig, metrics: Metrics) -> Result<UpstreamClient, reqwest::Error> {
+        let http = reqwest::Client::builder()
+            .user_agent(&config.user_agent)
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .connect_timeout(Duration::from_secs(config.connect_timeout_secs))
+            .build()?;
+
+        Ok(UpstreamClient {
+            http,
+            max_retries: config.max_retries,
+            retry_base_delay: Duration::from_millis(config.retry_base_delay_ms),
+            metrics,
+        })
+    }
+
+    /// GETs are idempotent so they're always safe to retry. The response of
+    /// the last attempt is handed back as is, status checking is on the caller.
+    /// With a limiter every attempt, retries included, waits on it first.
+    /// `upstream` and `endpoint` label the logs and metrics. Only the url's
+    /// host is logged, WG urls carry the application_id in the query.
+    #[tracing::instrument(
+        name = "upstream_request",
+        skip(self, url, limiter),
+        fields(host = %url_host(url), status, latency_ms, attempts)
+    )]
+    pub async fn get(
+        &self,
+        upstream: Upstream,
+        endpoint: &str,
+        url: &str,
+        limiter: Option<&RateLimiter>,
+    ) -> Result<Response, reqwest::Error> {
+        let span = Span::current();
+        let start = Instant::now();
+        let mut attempt = 0;
+        loop {
+            if let Some(limiter) = limiter {
+                limiter.acquire().await;
+            }
+            let result = self.http.get(url).send().await;
+
+            let retryable = match &result {
+                Ok(response) => is_retryable_status(response.status()),
+                Err(e) => e.is_timeout() || e.is_connect(),
+            };
+
+            if !retryable || attempt >= self.max_retries {
+                let latency = start.elapsed();
+                span.record("attempts", attempt + 1);
+                span.record("latency_ms", latency.as_millis() as u64);
+                let status = match &result {
+                    Ok(response) => {
+                        span.record("status", response.status().as_u16());
+                        debug!("Upstream request finished");
+                        response.status().as_u16().to_string()
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "Upstream request failed");
+                        String::from("error")
+                    }
+                };
+                self.metrics
+                    .observe_upstream(upstream.label(), endpoint, &status, latency);
+                return result;
+            }
+
+            debug!(attempt, "Retrying upstream request");
+
+            tokio::time::sleep(self.backoff(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    /// `get`, then the status check and decoding, with every failure as an
+    /// [`UpstreamError`].
+    pub async fn get_json<T: DeserializeOwned>(
+        &self,
+        upstream: Upstream,
+        endpoint: &str,
+        url: &str,
+        limiter: Option<&RateLimiter>,
+    ) -> Result<T, UpstreamError> {
+        let response = self
+            .get(upstream, endpoint, url, limiter)
+            .await
+            .map_err(|e| UpstreamError::from_reqwest(upstream, endpoint, e))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(UpstreamError::from_status(upstream, endpoint, status));
+        }
+        let body = response
+            .bytes()
+            .await
+            .map_err(|e| UpstreamError::from_reqwest(upstream, endpoint, e))?;
+        // the path says where in the payload decoding broke, ex "data.battles"
+        serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_slice(&body))
+            .map_err(|e| UpstreamError::decode(upstream, endpoint, e))
+    }
+
+    // exponential backoff with up to 50% jitter so parallel fetches that
+    // failed together don't all retry at the same instant
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .retry_base_delay
+            .saturating_mul(2u32.pow(attempt.min(16)))
+            .min(MAX_RETRY_DELAY);
+        let max_jitter = delay.as_millis() as u64 / 2;
+        delay + Duration::from_millis(rand::thread_rng().gen_range(0..=max_jitter))
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+fn url_host(url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .unwrap_or_default()
+}
+
+#[derive(Clone)]
+pub struct TomatoClient {
+    http: UpstreamClient,
+    config: TomatoConfig,
+}
+
+impl TomatoClient {
+    pub fn new(http: UpstreamClient, config: &Config) -> TomatoClient {
+        TomatoClient {
+            http,
+            config: config.tomato.clone(),
+        }
+    }
+
+    pub fn api_base(&self) -> &str {
+        &self.config.api_base
+    }
+
+    pub fn legacy_api_base(&self) -> &str {
+        &self.config.legacy_api_base
+    }
+
+    /// `region` only labels the error, it's already part of the url.
+    pub async fn get_json<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        region: Option<Region>,
+    ) -> Result<T, UpstreamError> {
+        self.http
+            .get_json(Upstream::Tomato, &self.endpoint_label(url), url, None)
+            .await
+            .map_err(|e| match region {
+                Some(region) => e.in_region(region),
+                None => e,
+            })
+    }
+
+    // first path segment past the base, ex "overall" or "moe"
+    fn endpoint_label(&self, url: &str) -> String {
+        let path = url
+            .strip_prefix(self.api_base())
+            .or_else(|| url.strip_prefix(self.legacy_api_base()))
+            .unwrap_or(url);
+        path.trim_start_matches('/')
+            .split(['/', '?'])
+            .next()
+            .unwrap_or_default()
+            .to_owned()
+    }
+}
+
+type BatchData = HashMap<String, Option<Value>>;
+
+#[derive(Clone)]
+pub struct WargamingClient {
+    http: UpstreamClient,
+    config: WargamingConfig,
+    // keyed by application_id
+    limiters: Arc<HashMap<String, RateLimiter>>,
+    batcher: Arc<AccountBatcher>,
+}
+
+impl WargamingClient {
+    pub fn new(http: UpstreamClient, config: &Config) -> WargamingClient {
+        let wargaming = &config.wargaming;
+        let mut limiters = HashMap::new();
+        for application_id in [
+            &wargaming.account_application_id,
+            &wargaming.clan_application_id,
+        ] {
+            limiters.entry(application_id.clone()).or_insert_with(|| {
+                RateLimiter::new(wargaming.requests_per_second, wargaming.burst)
+            });
+        }
+
+        WargamingClient {
+            http,
+            config: wargaming.clone(),
+            limiters: Arc::new(limiters),
+            batcher: Arc::new(AccountBatcher::new(wargaming.max_batch_size)),
+        }
+    }
+
+    pub fn host(&self, region: &Region) -> &str {
+        self.config.host(region)
+    }
+
+    pub fn account_application_id(&self) -> &str {
+        &self.config.account_application_id
+    }
+
+    pub fn clan_application_id(&self) -> &str {
+        &self.config.clan_application_id
+    }
+
+    /// Rate limited by the application_id in the url's query. Unwraps WG's
+    /// envelope, errors reported inside it come back as [`UpstreamError`]s
+    /// like any other.
+    pub async fn get_json<T: DeserializeOwned + Default>(
+        &self,
+        url: &str,
+        region: Region,
+    ) -> Result<T, UpstreamError> {
+        let parsed = Url::parse(url).ok();
+        let limiter = parsed.as_ref().and_then(|parsed| {
+            parsed
+                .query_pairs()
+                .find(|(key, _)| key == "application_id")
+                .and_then(|(_, application_id)| self.limiters.get(application_id.as_ref()))
+        });
+        // ex "/wot/clans/accountinfo/" becomes "clans/accountinfo", hosts
+        // can carry a path of their own in front
+        let endpoint = parsed
+            .as_ref()
+            .map(|parsed| {
+                let path = parsed.path();
+                path.split_once("/wot/")
+                    .map_or(path, |(_, endpoint)| endpoint)
+                    .trim_matches('/')
+                    .to_owned()
+            })
+            .unwrap_or_default();
+        self.http
+            .get_json::<WgResponse<T>>(Upstream::Wargaming, &endpoint, url, limiter)
+            .await
+            .and_then(|response| {
+                response
+                    .into_result()
+                    .map_err(|e| UpstreamError::wargaming(&endpoint, e))
+            })
+            .map_err(|e| e.in_region(region))
+    }
+
+    /// Looks up one account on an endpoint that takes a list of account_ids
+    /// (clans/accountinfo, account/info...). Lookups arriving within the
+    /// batch window get merged into a single request, the account's entry
+    /// of `data` is handed back.
+    pub async fn get_account_batched(
+        &self,
+        method: &'static str,
+        application_id: &str,
+        region: &Region,
+        account_id: u32,
+    ) -> BatchResult {
+        let key: BatchKey = (method, application_id.to_owned(), *region);
+        let (sender, receiver) = oneshot::channel();
+
+        match self.batcher.enqueue(key.clone(), account_id, sender) {
+            Enqueued::First(id) => {
+                let client = self.clone();
+                let window = Duration::from_millis(self.config.batch_window_ms);
+                tokio::spawn(async move {
+                    tokio::time::sleep(window).await;
+                    if let Some(batch) = client.batcher.take(&key, id) {
+                        client.flush_batch(key, batch).await;
+                    }
+                });
+            }
+            Enqueued::Full(batch) => {
+                let client = self.clone();
+                tokio::spawn(async move { client.flush_batch(key, batch).await });
+            }
+            Enqueued::Waiting => {}
+        }
+
+        receiver.await.unwrap_or_else(|_| {
+            // the flush task went away without answering
+            Err(
+                UpstreamError::new(Upstream::Wargaming, method, UpstreamErrorKind::Unavailable)
+                    .in_region(*region),
+            )
+        })
+    }
+
+    async fn flush_batch(
+        &self,
+        (method, application_id, region): BatchKey,
+        batch: PendingBatch,
+    ) {
+        let account_ids = batch
+            .account_ids()
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<String>>()
+            .join(",");
+        let url = format!(
+            "{}/wot/{}/?application_id={}&account_id={}",
+            self.host(&region),
+            method,
+            application_id,
+            account_ids
+        );
+
+        match self.get_json::<BatchData>(&url, region).await {
+            Ok(data) => batch.resolve(data),
+            Err(e) => batch.fail(e),
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::Context;
+use poise::serenity_prelude::{self as serenity, CreateEmbed};
+use std::time::{Duration, UNIX_EPOCH};
+use strum::IntoEnumIterator;
+use tomatobot::player_stats::player::{parse_suggestion, Candidate, Player};
+use tomatobot::service::PlayerLookup;
+use tomatobot::{Error, Region};
+
+/// The player `user` names, asking the author to pick when several regions
+/// have one. A name picked from autocomplete already says which region it's
+/// in. None once the author has been told why there's no player, failed
+/// lookups are left for the error handler to tell them about.
+pub async fn resolve_player(
+    ctx: Context<'_>,
+    user: &str,
+    region: Option<Region>,
+) -> Result<Option<(Region, Player)>, Error> {
+    let (user, suggested) = parse_suggestion(user);
+    let lookup = ctx
+        .data()
+        .service
+        .find_player(user, region.or(suggested))
+        .await?;
+    match lookup {
+        PlayerLookup::Found(region, player) => Ok(Some((region, player))),
+        PlayerLookup::Ambiguous {
+            candidates,
+            unchecked,
+        } => pick_candidate(ctx, user, candidates, &unchecked).await,
+        PlayerLookup::NotFound => {
+            ctx.say(format!("No player found called {}", user)).await?;
+            Ok(None)
+        }
+    }
+}
+
+fn candidates_embed(nickname: &str, candidates: &[Candidate], unchecked: &[Region]) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    match candidates.len() {
+        1 => embed.title(format!("Is this the {} you meant?", nickname)),
+        _ => embed.title(format!("There's a {} in several regions", nickname)),
+    };
+    let mut description = String::from("Pick the one you meant");
+    if !unchecked.is_empty() {
+        let regions: Vec<&str> = unchecked.iter().map(|region| region.name()).collect();
+        description.push_str(&format!(
+            "\nCouldn't check {} right now, the {} you meant might be there. \
+             Try again in a bit, or pick the region yourself.",
+            regions.join(", "),
+            nickname
+        ));
+    }
+    embed.description(description);
+
+    for candidate in candidates {
+        let battles = candidate
+            .battles
+            .map_or(String::from("unknown"), |battles| format!("`{}`", battles));
+        // Discord shows these relative to the reader, "3 days ago"
+        let last_battle = candidate
+            .last_battle_time
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(String::from("unknown"), |since| {
+                format!("<t:{}:R>", since.as_secs())
+            });
+        embed.field(
+            format!(
+                "{} ({})",
+                candidate.player.nickname,
+                candidate.region.name()
+            ),
+            format!("Battles: {}\nLast Battle: {}", battles, last_battle),
+            true,
+        );
+    }
+    embed
+}
+
+/// Asks the author which of the candidates they meant, one button per
+/// region, mentioning the `unchecked` regions. None when they don't pick
+/// within a minute.
+pub async fn pick_candidate(
+    ctx: Context<'_>,
+    nickname: &str,
+    candidates: Vec<Candidate>,
+    unchecked: &[Region],
+) -> Result<Option<(Region, Player)>, Error> {
+    let embed = candidates_embed(nickname, &candidates, unchecked);
+    // held until the buttons are gone, shutdown waits for that edit
+    let shutdown = &ctx.data().shutdown;
+    let _in_flight = shutdown.track();
+    let _collector = ctx.data().metrics.collector_guard();
+    let prompt = ctx
+        .send(|f| {
+            f.embed(|f| {
+                f.clone_from(&embed);
+                f
+            })
+            .components(|c| {
+                c.create_action_row(|ar| {
+                    for candidate in &candidates {
+                        ar.create_button(|b| {
+                            b.custom_id(candidate.region.name())
+                                .style(serenity::ButtonStyle::Primary)
+                                .label(candidate.region.name())
+                        });
+                    }
+                    ar
+                })
+            })
+        })
+        .await?;
+
+    let message_id = prompt.message().await?.id;
+    let picked = tokio::select! {
+        mci = serenity::CollectComponentInteraction::new(ctx)
+            .author_id(ctx.author().id)
+            .channel_id(ctx.channel_id())
+            .timeout(Duration::from_secs(60))
+            .filter(move |mci| mci.message.id == message_id) => mci,
+        _ = shutdown.requested() => None,
+    };
+
+    let Some(mci) = picked else {
+        prompt
+            .edit(ctx, |f| f.content("No player picked").components(|c| c))
+            .await?;
+        return Ok(None);
+    };
+    mci.create_interaction_response(ctx, |ir| {
+        ir.kind(serenity::InteractionResponseType::DeferredUpdateMessage)
+    })
+    .await?;
+
+    let region = Region::iter().find(|region| region.name() == mci.data.custom_id);
+    let Some(candidate) = candidates
+        .into_iter()
+        .find(|candidate| Some(candidate.region) == region)
+    else {
+        return Ok(None);
+    };
+    prompt
+        .edit(ctx, |f| {
+            f.content(format!(
+                "Showing {} ({})",
+                candidate.player.nickname,
+                candidate.region.name()
+            ))
+            .components(|c| c)
+        })
+        .await?;
+    Ok(Some((candidate.region, candidate.player)))
+}
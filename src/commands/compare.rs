@@ -0,0 +1,62 @@
+use crate::commands::candidates::resolve_player;
+use crate::commands::in_command_span;
+use crate::commands::stats::autocomplete_user;
+use crate::Context;
+use tokio::join;
+use tomatobot::player_stats::compare::generate_compare_embed;
+use tomatobot::player_stats::player::Period;
+use tomatobot::{Error, Region};
+
+/// Two players side by side, overall and over a period
+#[poise::command(slash_command)]
+pub async fn compare(
+    ctx: Context<'_>,
+    #[description = "First Players Username"]
+    #[autocomplete = "autocomplete_user"]
+    player_a: String,
+    #[description = "Second Players Username"]
+    #[autocomplete = "autocomplete_user"]
+    player_b: String,
+    #[description = "Select a Region"] region: Option<Region>,
+    #[description = "Period to Compare, 30 Days when left out"] period: Option<Period>,
+) -> Result<(), Error> {
+    in_command_span(ctx, async move {
+        ctx.defer().await?;
+        let service = &ctx.data().service;
+
+        let Some((region_a, player_a)) = resolve_player(ctx, &player_a, region).await? else {
+            return Ok(());
+        };
+        let Some((region_b, player_b)) = resolve_player(ctx, &player_b, region).await? else {
+            return Ok(());
+        };
+
+        // no quick first embed like /stats, both sides need to be there, and
+        // the clans aren't shown
+        let (data_a, data_b) = join!(
+            service.player_stats(region_a, player_a),
+            service.player_stats(region_b, player_b)
+        );
+
+        for data in [&data_a, &data_b] {
+            if let Some(message) = data.missing_stats_message(None) {
+                ctx.say(format!("{}: {}", data.player.nickname, message))
+                    .await?;
+                return Ok(());
+            }
+        }
+
+        let embed =
+            generate_compare_embed(&data_a, &data_b, period.unwrap_or(Period::R30DAYS)).await?;
+        ctx.send(|f| {
+            f.embed(|f| {
+                f.clone_from(&embed);
+                f
+            })
+        })
+        .await?;
+
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,76 @@
+use crate::commands::candidates::resolve_player;
+use crate::commands::in_command_span;
+use crate::Context;
+use tomatobot::store::LinkedAccount;
+use tomatobot::{Error, Region};
+
+// only the caller needs to see these
+async fn reply(ctx: Context<'_>, content: String) -> Result<(), Error> {
+    ctx.send(|f| f.content(content).ephemeral(true)).await?;
+    Ok(())
+}
+
+/// Link your Discord account to a WoT account, /stats uses it by default
+#[poise::command(slash_command)]
+pub async fn link(
+    ctx: Context<'_>,
+    #[description = "Players Username"] nickname: String,
+    #[description = "Select a Region"] region: Option<Region>,
+) -> Result<(), Error> {
+    in_command_span(ctx, async move {
+        ctx.defer_ephemeral().await?;
+
+        let Some((region, player)) = resolve_player(ctx, &nickname, region).await? else {
+            return Ok(());
+        };
+
+        let account = LinkedAccount {
+            region,
+            account_id: player.account_id,
+            nickname: player.nickname,
+        };
+        ctx.data().store.link(ctx.author().id.0, &account).await?;
+
+        reply(
+            ctx,
+            format!(
+                "Linked to **{}** ({}), /stats without a username shows this account now",
+                account.nickname,
+                account.region.name()
+            ),
+        )
+        .await
+    })
+    .await
+}
+
+/// Remove the WoT account linked with /link
+#[poise::command(slash_command)]
+pub async fn unlink(ctx: Context<'_>) -> Result<(), Error> {
+    in_command_span(ctx, async move {
+        let message = match ctx.data().store.unlink(ctx.author().id.0).await? {
+            true => "Unlinked your account",
+            false => "You don't have a linked account",
+        };
+        reply(ctx, message.to_owned()).await
+    })
+    .await
+}
+
+/// Show the WoT account linked with /link
+#[poise::command(slash_command)]
+pub async fn whoami(ctx: Context<'_>) -> Result<(), Error> {
+    in_command_span(ctx, async move {
+        let message = match ctx.data().store.linked_account(ctx.author().id.0).await? {
+            Some(account) => format!(
+                "You're linked to **{}** ({}), account ID `{}`",
+                account.nickname,
+                account.region.name(),
+                account.account_id
+            ),
+            None => String::from("You don't have a linked account, use /link to add one"),
+        };
+        reply(ctx, message).await
+    })
+    .await
+}
@@ -1,3 +1,28 @@
+pub mod candidates;
 pub mod clanstats;
+pub mod compare;
+pub mod link;
 pub mod marks;
 pub mod stats;
+
+use crate::Context;
+use std::future::Future;
+use tomatobot::Error;
+use tracing::{info_span, Instrument};
+
+/// Runs a command's body in a "command" span, so everything it fetches is
+/// logged under the command that asked for it. The name is poise's, it can't
+/// drift from what's registered.
+pub async fn in_command_span<F>(ctx: Context<'_>, body: F) -> Result<(), Error>
+where
+    F: Future<Output = Result<(), Error>>,
+{
+    let span = info_span!(
+        "command",
+        command = %ctx.command().qualified_name,
+        guild = ?ctx.guild_id(),
+        user = %ctx.author().id,
+        args = %ctx.invocation_string(),
+    );
+    body.instrument(span).await
+}
@@ -1,369 +1,21 @@
-use super::clanstats::{fetch_all_clan, generate_clan_embed, ClanData};
-use crate::errors::{
-    ClanInfoFetchError, CreateMainStatEmbedError, CreatePeriodEmbedError, FetchUserIDError,
-};
-use crate::player_stats::recents::{fetch_recent_data, RecentsData};
-use crate::{get_short_position, get_wn8_color};
-use crate::{
-    player_stats::{
-        overall::{fetch_overall_data, OverallData},
-        recents::TimeFrame,
-    },
-    Context, Error, Region,
-};
+use crate::commands::candidates::resolve_player;
+use crate::commands::in_command_span;
+use crate::Context;
 use poise::serenity_prelude::{
-    ComponentType, CreateComponents, CreateEmbed, CreateSelectMenu, CreateSelectMenuOption,
-    CreateSelectMenuOptions,
+    self as serenity, ComponentType, CreateComponents, CreateEmbed, CreateSelectMenu,
+    CreateSelectMenuOption, CreateSelectMenuOptions,
 };
-use serde::Deserialize;
-use std::collections::HashMap;
+use poise::AutocompleteChoice;
 use std::str::FromStr;
 use strum::IntoEnumIterator;
-use strum_macros::EnumIter;
-use tokio::join;
-
-#[derive(Deserialize)]
-pub struct ClanInfoResponse {
-    data: HashMap<String, Option<PlayerAccountInfo>>,
-}
-
-//TODO rename this so that its clear its for clan data
-#[derive(Deserialize, Clone)]
-pub struct PlayerAccountInfo {
-    clan: PlayerClanInfo,
-    role: String,
-}
-
-#[derive(Deserialize, Clone)]
-pub struct PlayerClanInfo {
-    tag: String,
-    emblems: Emblems,
-    clan_id: u32,
-}
-
-#[derive(Deserialize, Clone, Default)]
-pub struct Emblems {
-    pub x64: EmblemURL,
-}
-
-#[derive(Debug, Deserialize, Clone, Default)]
-pub struct EmblemURL {
-    pub portal: String,
-}
-
-#[derive(Deserialize)]
-pub struct UserSearch {
-    #[serde(default)]
-    data: Vec<Player>,
-}
-
-#[derive(Deserialize, Clone, Default)]
-pub struct Player {
-    pub nickname: String,
-    pub account_id: u32,
-}
-
-#[derive(Default)]
-pub struct PlayerData {
-    player_clan: Option<PlayerAccountInfo>,
-    clan: Option<ClanData>,
-    player: Player,
-    region: Region,
-    overall: Option<OverallData>,
-    recents: Option<RecentsData>,
-    is_in_clan: bool,
-}
-
-impl PlayerData {
-    pub fn get_period_data(&self, period: Period) -> Option<&TimeFrame> {
-        if let Some(recents) = &self.recents {
-            match period {
-                Period::R24HR => {
-                    return Some(&recents.recent24hr);
-                }
-                Period::R3DAYS => {
-                    return Some(&recents.recent3days);
-                }
-                Period::R7DAYS => {
-                    return Some(&recents.recent7days);
-                }
-                Period::R30DAYS => {
-                    return Some(&recents.recent30days);
-                }
-                Period::R60DAYS => {
-                    return Some(&recents.recent60days);
-                }
-                Period::R1000BATTLES => {
-                    return Some(&recents.recent1000battles);
-                }
-                Period::R100BATTLES => {
-                    return Some(&recents.recent100battles);
-                }
-            }
-        }
-        None
-    }
-}
-
-#[derive(poise::ChoiceParameter, Clone, Copy, EnumIter)]
-pub enum Period {
-    R24HR,
-    R3DAYS,
-    R7DAYS,
-    R30DAYS,
-    R60DAYS,
-    R1000BATTLES,
-    R100BATTLES,
-}
-
-impl Period {
-    fn nice_name(&self) -> &str {
-        match self {
-            Period::R24HR => "24 Hours",
-            Period::R3DAYS => "3 Days",
-            Period::R7DAYS => "7 Days",
-            Period::R30DAYS => "30 Days",
-            Period::R60DAYS => "60 Days",
-            Period::R1000BATTLES => "1000 Battles",
-            Period::R100BATTLES => "100 Battles",
-        }
-    }
-}
-
-pub async fn fetch_user_id(
-    input: &str,
-    region: Region,
-) -> Result<Option<Player>, FetchUserIDError> {
-    let wot_user_url = format!(
-        "https://api.worldoftanks.{}/wot/account/list/?language=en&application_id=42d1c07ba19a98fcbfdf5f3492bff972&search={}",
-        region.extension(),
-        input
-    );
-
-    let response = reqwest::get(wot_user_url)
-        .await?
-        .json::<UserSearch>()
-        .await?;
-
-    let player = &response.data.get(0);
-    Ok(player.cloned())
-}
-
-pub async fn fetch_clan_info(
-    region: &Region,
-    account_id: &u32,
-) -> Result<Option<PlayerAccountInfo>, ClanInfoFetchError> {
-    let clan_info_url = format!(
-        "https://api.worldoftanks.{}/wot/clans/accountinfo/?application_id=20e1e0e4254d98635796fc71f2dfe741&account_id={}",
-        region.extension(),
-        account_id
-    );
-
-    let response = reqwest::get(clan_info_url)
-        .await?
-        .json::<ClanInfoResponse>()
-        .await?;
-
-    if let Some(output) = response.data.get(&account_id.to_string()) {
-        return Ok(output.clone());
-    }
-
-    Ok(None)
-}
-
-pub async fn find_user_server(user: &str) -> Option<(Region, Player)> {
-    let (na, eu, asia) = join!(
-        fetch_user_id(user, Region::NA),
-        fetch_user_id(user, Region::EU),
-        fetch_user_id(user, Region::ASIA)
-    );
-
-    if let Ok(na_response) = na {
-        if let Some(player) = na_response {
-            return Some((Region::NA, player));
-        }
-    }
-
-    if let Ok(eu_response) = eu {
-        if let Some(player) = eu_response {
-            return Some((Region::EU, player));
-        }
-    }
-
-    if let Ok(asia_response) = asia {
-        if let Some(player) = asia_response {
-            return Some((Region::ASIA, player));
-        }
-    }
-
-    None
-}
-
-pub async fn generate_period_embed(
-    player_data: &PlayerData,
-    period: Period,
-) -> Result<CreateEmbed, CreatePeriodEmbedError> {
-    const DISPLAY_AMMOUNT: usize = 5;
-    let mut embed = CreateEmbed::default();
-    let data;
-
-    match player_data.get_period_data(period) {
-        Some(player_data) => {
-            data = player_data;
-        }
-        None => {
-            return Err(CreatePeriodEmbedError::MissingRecentsError);
-        }
-    }
-
-    let mut tanks = data.tank_stats.clone();
-
-    embed
-        .title(format!("{}'s Stats", player_data.player.nickname))
-        .description(format!("**Last {} Stats**", period.nice_name()))
-        .field(
-            "Totals",
-            format!(
-                "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
-                data.overall.battles, data.overall.wn8, data.overall.winrate, data.overall.tier
-            ),
-            true,
-        )
-        .color(get_wn8_color(data.overall.wn8));
-
-    tanks.sort_by_key(|tank| tank.battles);
-    tanks.reverse();
-    tanks.truncate(DISPLAY_AMMOUNT);
-
-    for tank in tanks {
-        embed.field(
-            format!("{}", tank.name),
-            format!(
-                "Battles: `{}`\nWin Rate: `{}%`\n WN8: `{}`\n DPG: `{}`",
-                tank.battles, tank.win_rate, tank.wn8, tank.dpg
-            ),
-            true,
-        );
-    }
-    Ok(embed)
-}
-
-pub async fn generate_main_stat_embed(
-    data: &PlayerData,
-) -> Result<CreateEmbed, CreateMainStatEmbedError> {
-    let overall;
-    let recents;
-
-    match &data.overall {
-        Some(data) => {
-            overall = data;
-        }
-        None => return Err(CreateMainStatEmbedError::MissingOverallError),
-    }
-
-    match &data.recents {
-        Some(data) => {
-            recents = data;
-        }
-        None => return Err(CreateMainStatEmbedError::MissingRecentsError),
-    }
-
-    let mut embed = CreateEmbed::default();
-
-    embed.title(format!("{}'s Stats", data.player.nickname));
-    embed.url(format!(
-        "https://tomato.gg/stats/{}/{}={}",
-        data.region.name(),
-        data.player.nickname,
-        data.player.account_id
-    ));
-
-    embed.field(
-        "**Overall**",
-        format!(
-            "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
-            overall.battles, overall.wn8, overall.win_rate, overall.tier
-        ),
-        true,
-    );
-    embed.field(
-        "**24 Hours**",
-        format!(
-            "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
-            recents.recent24hr.overall.battles,
-            recents.recent24hr.overall.wn8,
-            recents.recent24hr.overall.winrate,
-            recents.recent24hr.overall.tier
-        ),
-        true,
-    );
-
-    embed.field(
-        "**7 Days**",
-        format!(
-            "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
-            recents.recent7days.overall.battles,
-            recents.recent7days.overall.wn8,
-            recents.recent7days.overall.winrate,
-            recents.recent7days.overall.tier
-        ),
-        true,
-    );
-
-    embed.field(
-        "**30 Days**",
-        format!(
-            "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
-            recents.recent30days.overall.battles,
-            recents.recent30days.overall.wn8,
-            recents.recent30days.overall.winrate,
-            recents.recent30days.overall.tier
-        ),
-        true,
-    );
-
-    embed.field(
-        "**60 Days**",
-        format!(
-            "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
-            recents.recent60days.overall.battles,
-            recents.recent60days.overall.wn8,
-            recents.recent60days.overall.winrate,
-            recents.recent60days.overall.tier
-        ),
-        true,
-    );
-
-    embed.field(
-        "**1000 Battles**",
-        format!(
-            "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
-            recents.recent1000battles.overall.battles,
-            recents.recent1000battles.overall.wn8,
-            recents.recent1000battles.overall.winrate,
-            recents.recent1000battles.overall.tier
-        ),
-        true,
-    );
-    embed.footer(|f| {
-        f.text("Powered by Tomato.gg");
-        f.icon_url("https://tomato.gg/_next/image?url=%2Ftomato.png&w=48&q=75");
-        f
-    });
-    embed.color(get_wn8_color(overall.wn8));
-    match &data.player_clan {
-        Some(clan_info) => {
-            embed.thumbnail(&clan_info.clan.emblems.x64.portal);
-            embed.description(format!(
-                "**{} at [{}]**",
-                get_short_position(&clan_info.role),
-                &clan_info.clan.tag
-            ));
-        }
-        None => {}
-    }
-    Ok(embed)
-}
+use tomatobot::clans::generate_clan_embed;
+use tomatobot::player_stats::player::{
+    generate_main_stat_embed, generate_period_embed, suggestion_value, Period, Player, PlayerData,
+};
+use tomatobot::service::AUTOCOMPLETE_LIMIT;
+use tomatobot::store::LinkedAccount;
+use tomatobot::{Error, Region};
+use tracing::debug;
 
 pub fn add_options(menu: &mut CreateSelectMenu) -> &mut CreateSelectMenu {
     let mut options = CreateSelectMenuOptions::default();
@@ -405,99 +57,140 @@ pub fn create_all_components(component: &mut CreateComponents) -> &mut CreateCom
         })
 }
 
+// shown in place of the embed once the uncached pass has nothing either
+fn missing_stats_title(data: &PlayerData, period: Option<Period>) -> String {
+    data.missing_stats_message(period)
+        .unwrap_or_else(|| String::from("Player Not Found on Tomato.gg"))
+}
+
+// the region picked so far, autocomplete sees the other options as typed
+fn chosen_region(ctx: Context<'_>) -> Option<Region> {
+    let poise::Context::Application(ctx) = ctx else {
+        return None;
+    };
+    let index = ctx
+        .interaction
+        .data()
+        .options
+        .iter()
+        .find(|option| option.name == "region")?
+        .value
+        .as_ref()?
+        .as_u64()?;
+    Region::iter().nth(index as usize)
+}
+
+pub async fn autocomplete_user(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice<String>> {
+    // one search per keystroke, every region each time would triple the WG
+    // calls. Without a region picked, the author's linked one is the best guess
+    let region = match chosen_region(ctx) {
+        Some(region) => region,
+        None => match ctx.data().store.linked_account(ctx.author().id.0).await {
+            Ok(Some(linked)) => linked.region,
+            _ => Region::default(),
+        },
+    };
+
+    match ctx.data().service.search_players(region, partial).await {
+        Ok(players) => players
+            .into_iter()
+            .take(AUTOCOMPLETE_LIMIT)
+            .map(|player| AutocompleteChoice {
+                name: format!("{} ({})", player.nickname, region.name()),
+                value: suggestion_value(&player.nickname, region),
+            })
+            .collect(),
+        Err(e) => {
+            debug!(?region, "Autocomplete search failed: {}", e);
+            Vec::new()
+        }
+    }
+}
+
 #[poise::command(slash_command)]
 pub async fn stats(
     ctx: Context<'_>,
-    #[description = "Players Username"] user: String,
+    #[description = "Players Username, your linked account when left out"]
+    #[autocomplete = "autocomplete_user"]
+    user: Option<String>,
     #[description = "Select a Region"] region: Option<Region>,
     #[description = "Detailed Stats for a Period"] period: Option<Period>,
 ) -> Result<(), Error> {
-    ctx.defer().await?;
-    let user_info;
-    let user_region: Region;
-
-    match region {
-        Some(region) => {
-            user_region = region;
-
-            // this cluster fuck is becuase fetch_user_id is a Result<Option>
-            // so have to handle both the error case of the Result and the
-            // None case from the option cant just propagate the error up
-            // because this function is handled by poise
-
-            match fetch_user_id(user.as_str(), user_region).await {
-                Ok(player_response) => match player_response {
-                    Some(player) => {
-                        user_info = player;
-                    }
-                    None => {
-                        ctx.say("No player found with that name").await?;
-                        return Ok(());
-                    }
-                },
-                Err(e) => {
-                    ctx.say(format!("Error Fetching User Id {}", e)).await?;
-                    return Ok(());
-                }
-            }
-        }
-
-        None => {
-            let response = find_user_server(&user).await;
-            match response {
-                Some((server, player)) => {
-                    user_info = player;
-                    user_region = server;
-                }
+    in_command_span(ctx, async move {
+        ctx.defer().await?;
+
+        let (user_region, user_info) = match user {
+            Some(user) => match resolve_player(ctx, &user, region).await? {
+                Some(found) => found,
+                None => return Ok(()),
+            },
+            // the linked account already knows its region
+            None => match ctx.data().store.linked_account(ctx.author().id.0).await? {
+                Some(linked) => linked_player(linked),
                 None => {
-                    ctx.say("No Player found with that name").await?;
+                    ctx.say("Give a username, or link your account with /link first")
+                        .await?;
                     return Ok(());
                 }
-            }
-        }
-    }
-
-    let mut all_data = PlayerData {
-        player_clan: None,
-        clan: None,
-        player: user_info,
-        region: user_region,
-        overall: None,
-        recents: None,
-        is_in_clan: false,
-    };
+            },
+        };
 
-    let (cached_overalls_fetch, cached_recents_fetch, player_clan_fetch) = join!(
-        fetch_overall_data(&user_region, &all_data.player, true),
-        fetch_recent_data(&user_region, &all_data.player, true),
-        fetch_clan_info(&user_region, &all_data.player.account_id)
-    );
-
-    match player_clan_fetch {
-        Ok(data) => {
-            all_data.player_clan = data;
-        }
-
-        Err(e) => {
-            println!("{}", e);
-        }
-    }
-
-    match cached_overalls_fetch {
-        Ok(data) => all_data.overall = data,
+        send_stats(ctx, user_region, user_info, period).await
+    })
+    .await
+}
 
-        Err(e) => {
-            println!("{}", e);
-        }
-    }
+/// Stats for the member's linked account, from their profile's context menu
+#[poise::command(context_menu_command = "View WoT Stats")]
+pub async fn view_stats(
+    ctx: Context<'_>,
+    #[description = "Member to show stats for"] member: serenity::User,
+) -> Result<(), Error> {
+    in_command_span(ctx, async move {
+        // the target isn't part of the invocation string
+        debug!(member = %member.id, "Looking up member's linked account");
+        let Some(linked) = ctx.data().store.linked_account(member.id.0).await? else {
+            ctx.send(|f| {
+                f.content(format!(
+                    "{} hasn't linked a WoT account with /link yet",
+                    member.name
+                ))
+                .ephemeral(true)
+            })
+            .await?;
+            return Ok(());
+        };
+
+        ctx.defer().await?;
+        let (region, player) = linked_player(linked);
+        send_stats(ctx, region, player, None).await
+    })
+    .await
+}
 
-    match cached_recents_fetch {
-        Ok(data) => all_data.recents = data,
+fn linked_player(linked: LinkedAccount) -> (Region, Player) {
+    let player = Player {
+        nickname: linked.nickname,
+        account_id: linked.account_id,
+    };
+    (linked.region, player)
+}
 
-        Err(e) => {
-            println!("{}", e);
-        }
-    }
+/// Sends the stats embed with its period menu and buttons, then keeps
+/// answering them until the collector times out.
+async fn send_stats(
+    ctx: Context<'_>,
+    user_region: Region,
+    user_info: Player,
+    period: Option<Period>,
+) -> Result<(), Error> {
+    // shutdown waits on this guard from the first reply on, so the message
+    // always gets its final edit and loses its components before the bot
+    // goes away
+    let shutdown = &ctx.data().shutdown;
+    let _in_flight = shutdown.track();
+    let service = &ctx.data().service;
+    let mut all_data = service.player_data(user_region, user_info).await;
 
     let mut embed: CreateEmbed;
 
@@ -534,80 +227,7 @@ pub async fn stats(
         })
         .await?;
 
-    match &all_data.player_clan {
-        Some(clan) => {
-            all_data.is_in_clan = true;
-            let (clan_data, overalls_fetch, recents_fetch) = join!(
-                fetch_all_clan(user_region, clan.clan.clan_id),
-                fetch_overall_data(&all_data.region, &all_data.player, false),
-                fetch_recent_data(&all_data.region, &all_data.player, false),
-            );
-
-            match clan_data {
-                Ok(data) => {
-                    all_data.clan = Some(data);
-                }
-
-                Err(e) => {
-                    println!("{}", e);
-                }
-            }
-
-            match overalls_fetch {
-                Ok(data) => {
-                    if data.is_some() {
-                        all_data.overall = data;
-                    }
-                }
-
-                Err(e) => {
-                    println!("{}", e);
-                }
-            }
-
-            match recents_fetch {
-                Ok(data) => {
-                    if data.is_some() {
-                        all_data.recents = data;
-                    }
-                }
-
-                Err(e) => {
-                    println!("{}", e);
-                }
-            }
-        }
-        None => {
-            let (overalls_fetch, recents_fetch) = join!(
-                fetch_overall_data(&all_data.region, &all_data.player, false),
-                fetch_recent_data(&all_data.region, &all_data.player, false),
-            );
-
-            match overalls_fetch {
-                Ok(data) => {
-                    if data.is_some() {
-                        all_data.overall = data;
-                    }
-                }
-
-                Err(e) => {
-                    println!("{}", e);
-                }
-            }
-
-            match recents_fetch {
-                Ok(data) => {
-                    if data.is_some() {
-                        all_data.recents = data;
-                    }
-                }
-
-                Err(e) => {
-                    println!("{}", e);
-                }
-            }
-        }
-    }
+    service.refresh_player_data(&mut all_data).await;
 
     match period {
         Some(period) => match generate_period_embed(&all_data, period).await {
@@ -617,7 +237,7 @@ pub async fn stats(
 
             Err(_) => {
                 embed = CreateEmbed::default()
-                    .title("User Not Found on Tomato.gg")
+                    .title(missing_stats_title(&all_data, Some(period)))
                     .to_owned();
                 message
                     .edit(ctx, |f| {
@@ -636,7 +256,7 @@ pub async fn stats(
 
             Err(_) => {
                 embed = CreateEmbed::default()
-                    .title("User Not Found on Tomato.gg")
+                    .title(missing_stats_title(&all_data, None))
                     .to_owned();
                 message
                     .edit(ctx, |f| {
@@ -671,13 +291,15 @@ pub async fn stats(
             .await?;
     }
     let message_id = message.message().await.unwrap().id;
-    while let Some(mci) = poise::serenity_prelude::CollectComponentInteraction::new(ctx)
-        .author_id(ctx.author().id)
-        .channel_id(ctx.channel_id())
-        .timeout(std::time::Duration::from_secs(120))
-        .filter(move |mci| mci.message.id == message_id)
-        .await
-    {
+    let _collector = ctx.data().metrics.collector_guard();
+    while let Some(mci) = tokio::select! {
+        mci = poise::serenity_prelude::CollectComponentInteraction::new(ctx)
+            .author_id(ctx.author().id)
+            .channel_id(ctx.channel_id())
+            .timeout(std::time::Duration::from_secs(120))
+            .filter(move |mci| mci.message.id == message_id) => mci,
+        _ = shutdown.requested() => None,
+    } {
         match mci.data.component_type {
             ComponentType::SelectMenu => {
                 let period = mci.data.values.first().unwrap();
@@ -709,7 +331,7 @@ pub async fn stats(
             ComponentType::Button => {
                 let player_id = "player";
                 let clan_id = "clan";
-                if &mci.data.custom_id == player_id {
+                if mci.data.custom_id == player_id {
                     embed = generate_main_stat_embed(&all_data).await.unwrap();
                     message
                         .edit(ctx, |f| {
@@ -720,19 +342,29 @@ pub async fn stats(
                             .components(create_all_components)
                         })
                         .await?;
-                } else if &mci.data.custom_id == clan_id {
-                    let new_embed = generate_clan_embed(all_data.clan.as_ref().unwrap()).await;
-
-                    message
-                        .edit(ctx, |f| {
-                            f.embeds.push(new_embed);
-                            f.components(create_all_components)
-                        })
-                        .await?;
+                } else if mci.data.custom_id == clan_id {
+                    match all_data.clan.as_ref() {
+                        Some(clan) => {
+                            let new_embed = generate_clan_embed(clan).await;
+                            message
+                                .edit(ctx, |f| {
+                                    f.embeds.push(new_embed);
+                                    f.components(create_all_components)
+                                })
+                                .await?;
+                        }
+                        None => {
+                            ctx.send(|f| {
+                                f.content("Couldn't load this player's clan stats, try again later")
+                                    .ephemeral(true)
+                            })
+                            .await?;
+                        }
+                    }
                 }
             }
-            _ => {
-                println!("cock");
+            other => {
+                debug!(component_type = ?other, "Ignoring unexpected component interaction");
             }
         }
 
@@ -741,7 +373,7 @@ pub async fn stats(
         })
         .await?;
     }
-    //removes buttons and select after timeout
+    //removes buttons and select after timeout or on shutdown
     message
         .edit(ctx, |f| {
             f.components(|c| c);
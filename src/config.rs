@@ -0,0 +1,274 @@
+use crate::errors::ConfigError;
+use crate::Region;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_CONFIG_PATH: &str = "config.toml";
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Config {
+    pub logging: LoggingConfig,
+    pub http: HttpConfig,
+    pub store: StoreConfig,
+    pub scheduler: SchedulerConfig,
+    pub server: ServerConfig,
+    pub shutdown: ShutdownConfig,
+    pub tomato: TomatoConfig,
+    pub wargaming: WargamingConfig,
+    pub emojis: EmojiConfig,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Pretty,
+    Json,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+    // EnvFilter directives, ex "info,learn_rust=debug"
+    pub filter: String,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig {
+            format: LogFormat::Pretty,
+            filter: String::from("info"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct HttpConfig {
+    pub user_agent: String,
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    // retries on top of the first attempt, only for transient failures
+    pub max_retries: u32,
+    pub retry_base_delay_ms: u64,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        HttpConfig {
+            user_agent: format!("TomatoBot/{}", env!("CARGO_PKG_VERSION")),
+            timeout_secs: 10,
+            connect_timeout_secs: 5,
+            max_retries: 3,
+            retry_base_delay_ms: 250,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct StoreConfig {
+    // sqlite file the cached datasets and linked accounts are persisted to
+    pub path: PathBuf,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        StoreConfig {
+            path: PathBuf::from("tomatobot.db"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    pub tank_data_interval_secs: u64,
+    pub tank_economics_interval_secs: u64,
+    pub recent_tank_stats_interval_secs: u64,
+    // failed refreshes retry after base * 2^n, capped at max
+    pub retry_base_delay_secs: u64,
+    pub retry_max_delay_secs: u64,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            tank_data_interval_secs: 36000,
+            tank_economics_interval_secs: 128000,
+            recent_tank_stats_interval_secs: 36000,
+            retry_base_delay_secs: 30,
+            retry_max_delay_secs: 1800,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ServerConfig {
+    // ops HTTP server (/metrics, /healthz, /readyz), on unless disabled
+    pub enabled: bool,
+    pub bind: SocketAddr,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            enabled: true,
+            bind: SocketAddr::from(([0, 0, 0, 0], 8080)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    // how long SIGTERM waits on open /stats messages before the shards get shut down anyway
+    pub timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig { timeout_secs: 10 }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct TomatoConfig {
+    // base for the v2 endpoints (moe, mastery, overall, recents...)
+    pub api_base: String,
+    // the clan endpoint still lives on the old api
+    pub legacy_api_base: String,
+}
+
+impl Default for TomatoConfig {
+    fn default() -> Self {
+        TomatoConfig {
+            api_base: String::from("https://api.tomato.gg/dev/api-v2"),
+            legacy_api_base: String::from("https://api.tomato.gg/api"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct WargamingConfig {
+    pub hosts: WargamingHosts,
+    // used for account/list lookups
+    pub account_application_id: String,
+    // used for everything clan related
+    pub clan_application_id: String,
+    // WG limits each application_id separately, these apply per app ID
+    pub requests_per_second: u32,
+    pub burst: u32,
+    // how long account_id lookups wait for others to share a request with
+    pub batch_window_ms: u64,
+    // WG accepts at most 100 account_ids per request
+    pub max_batch_size: usize,
+}
+
+impl Default for WargamingConfig {
+    fn default() -> Self {
+        WargamingConfig {
+            hosts: WargamingHosts::default(),
+            account_application_id: String::from("42d1c07ba19a98fcbfdf5f3492bff972"),
+            clan_application_id: String::from("20e1e0e4254d98635796fc71f2dfe741"),
+            requests_per_second: 10,
+            burst: 10,
+            batch_window_ms: 20,
+            max_batch_size: 100,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct WargamingHosts {
+    pub na: String,
+    pub eu: String,
+    pub asia: String,
+}
+
+impl Default for WargamingHosts {
+    fn default() -> Self {
+        WargamingHosts {
+            na: default_wg_host(&Region::NA),
+            eu: default_wg_host(&Region::EU),
+            asia: default_wg_host(&Region::ASIA),
+        }
+    }
+}
+
+fn default_wg_host(region: &Region) -> String {
+    format!("https://api.worldoftanks.{}", region.extension())
+}
+
+impl WargamingConfig {
+    pub fn host(&self, region: &Region) -> &str {
+        match region {
+            Region::NA => &self.hosts.na,
+            Region::EU => &self.hosts.eu,
+            Region::ASIA => &self.hosts.asia,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct EmojiConfig {
+    // look up the bot application's own emojis by name at startup, for any
+    // name not in ids
+    pub discover: bool,
+    // emoji name (ex "mark_3") to custom emoji ID
+    pub ids: HashMap<String, u64>,
+}
+
+impl Config {
+    /// Loads the config from `TOMATOBOT_CONFIG` (or `config.toml`), falling
+    /// back to the defaults when the file doesn't exist, then applies any
+    /// environment overrides on top.
+    pub fn load() -> Result<Config, ConfigError> {
+        let path = std::env::var("TOMATOBOT_CONFIG").unwrap_or(DEFAULT_CONFIG_PATH.to_owned());
+        let mut config = Config::from_file(Path::new(&path))?;
+        config.apply_env_overrides();
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let contents = std::fs::read_to_string(path)?;
+        Ok(toml::from_str(&contents)?)
+    }
+
+    fn apply_env_overrides(&mut self) {
+        let overrides: [(&str, &mut String); 7] = [
+            ("TOMATO_API_BASE", &mut self.tomato.api_base),
+            ("TOMATO_LEGACY_API_BASE", &mut self.tomato.legacy_api_base),
+            ("WG_HOST_NA", &mut self.wargaming.hosts.na),
+            ("WG_HOST_EU", &mut self.wargaming.hosts.eu),
+            ("WG_HOST_ASIA", &mut self.wargaming.hosts.asia),
+            (
+                "WG_ACCOUNT_APPLICATION_ID",
+                &mut self.wargaming.account_application_id,
+            ),
+            (
+                "WG_CLAN_APPLICATION_ID",
+                &mut self.wargaming.clan_application_id,
+            ),
+        ];
+
+        for (key, field) in overrides {
+            if let Ok(value) = std::env::var(key) {
+                *field = value;
+            }
+        }
+    }
+}
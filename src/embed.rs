@@ -0,0 +1,102 @@
+//! Embeds outside Discord: stable JSON for the snapshot tests and the CLI,
+//! and plain text for a terminal.
+
+use poise::serenity_prelude::CreateEmbed;
+use serde_json::{Map, Value};
+
+/// The embed's JSON with object keys sorted at every level. `CreateEmbed`
+/// keeps its fields in a `HashMap`, so without this the same embed can
+/// serialize differently from run to run.
+pub fn to_json(embed: &CreateEmbed) -> Value {
+    sorted(Value::Object(
+        embed
+            .0
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.clone()))
+            .collect(),
+    ))
+}
+
+fn sorted(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<(String, Value)> = map.into_iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key, sorted(value)))
+                    .collect::<Map<String, Value>>(),
+            )
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(sorted).collect()),
+        other => other,
+    }
+}
+
+/// Renders the embed for a terminal: title, link and description, then each
+/// field as a heading with its value indented underneath. Custom emojis
+/// become `:name:` and the markdown markers are dropped.
+pub fn to_text(embed: &CreateEmbed) -> String {
+    let json = to_json(embed);
+    let mut lines = Vec::new();
+
+    for key in ["title", "url", "description"] {
+        if let Some(value) = json[key].as_str() {
+            lines.push(plain(value));
+        }
+    }
+
+    for field in json["fields"].as_array().into_iter().flatten() {
+        lines.push(String::new());
+        lines.push(plain(field["name"].as_str().unwrap_or_default()));
+        for line in plain(field["value"].as_str().unwrap_or_default()).lines() {
+            lines.push(format!("  {}", line));
+        }
+    }
+
+    if let Some(footer) = json["footer"]["text"].as_str() {
+        lines.push(String::new());
+        lines.push(plain(footer));
+    }
+
+    lines.join("\n")
+}
+
+fn plain(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        match custom_emoji_name(rest) {
+            Some((name, len)) => {
+                out.push(':');
+                out.push_str(name);
+                out.push(':');
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out.replace("**", "").replace('`', "")
+}
+
+// `<:name:id>` or `<a:name:id>`, returns the name and the tag's length
+fn custom_emoji_name(text: &str) -> Option<(&str, usize)> {
+    let end = text.find('>')?;
+    let mut parts = text[1..end].split(':');
+    let prefix = parts.next()?;
+    let name = parts.next()?;
+    let id = parts.next()?;
+    let is_emoji = (prefix.is_empty() || prefix == "a")
+        && !name.is_empty()
+        && !id.is_empty()
+        && id.bytes().all(|b| b.is_ascii_digit())
+        && parts.next().is_none();
+    is_emoji.then_some((name, end + 1))
+}
@@ -0,0 +1,131 @@
+//! Custom emojis used in the embeds, looked up by name.
+//!
+//! Custom emoji IDs only work where the emoji exists, so each deployment
+//! configures its own under `[emojis.ids]` and can fill in the rest from the
+//! bot application's emojis. Anything still missing renders as plain
+//! Unicode or text instead of a broken `<:name:id>` tag.
+
+use crate::config::EmojiConfig;
+use serde::Deserialize;
+use std::collections::HashMap;
+use tracing::info;
+
+pub const DISCORD_API_BASE: &str = "https://discord.com/api/v10";
+
+/// Every emoji name the embeds use, with what to show when it's missing.
+const FALLBACKS: [(&str, &str); 29] = [
+    ("LT", "LT"),
+    ("MT", "MT"),
+    ("HT", "HT"),
+    ("TD", "TD"),
+    ("SPG", "SPG"),
+    ("premLT", "Premium LT"),
+    ("premMT", "Premium MT"),
+    ("premHT", "Premium HT"),
+    ("premTD", "Premium TD"),
+    ("premSPG", "Premium SPG"),
+    ("USA", "🇺🇸"),
+    ("USSR", "USSR"),
+    ("Germany", "🇩🇪"),
+    ("France", "🇫🇷"),
+    ("UK", "🇬🇧"),
+    ("China", "🇨🇳"),
+    ("Japan", "🇯🇵"),
+    ("Czech", "🇨🇿"),
+    ("Sweden", "🇸🇪"),
+    ("Poland", "🇵🇱"),
+    ("Italy", "🇮🇹"),
+    ("mark_1", "1 Mark"),
+    ("mark_2", "2 Marks"),
+    ("mark_3", "3 Marks"),
+    ("masteryIcon", "Ace"),
+    ("firstClassIcon", "1st"),
+    ("2ndClassIcon", "2nd"),
+    ("3rdClassIcon", "3rd"),
+    ("credits", "💰"),
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Emojis {
+    // name to `<:name:id>`
+    tags: HashMap<String, String>,
+}
+
+impl Emojis {
+    pub fn from_config(config: &EmojiConfig) -> Emojis {
+        let tags = config
+            .ids
+            .iter()
+            .map(|(name, id)| (name.clone(), format!("<:{}:{}>", name, id)))
+            .collect();
+        Emojis { tags }
+    }
+
+    /// Adds the application's emojis the embeds use, configured IDs win.
+    pub fn discover(&mut self, emojis: Vec<ApplicationEmoji>) {
+        let configured = self.tags.len();
+        for emoji in emojis {
+            if !FALLBACKS.iter().any(|(name, _)| *name == emoji.name) {
+                continue;
+            }
+            let tag = match emoji.animated {
+                true => format!("<a:{}:{}>", emoji.name, emoji.id),
+                false => format!("<:{}:{}>", emoji.name, emoji.id),
+            };
+            self.tags.entry(emoji.name).or_insert(tag);
+        }
+        info!(
+            configured,
+            discovered = self.tags.len() - configured,
+            used = FALLBACKS.len(),
+            "Loaded emojis"
+        );
+    }
+
+    /// The emoji tag for `name`, or its fallback when this deployment
+    /// doesn't have it.
+    pub fn get<'a>(&'a self, name: &'a str) -> &'a str {
+        if let Some(tag) = self.tags.get(name) {
+            return tag;
+        }
+        FALLBACKS
+            .iter()
+            .find(|(known, _)| *known == name)
+            .map_or(name, |(_, fallback)| fallback)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApplicationEmoji {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub animated: bool,
+}
+
+#[derive(Deserialize)]
+struct ApplicationEmojis {
+    items: Vec<ApplicationEmoji>,
+}
+
+/// Lists the emojis uploaded to the bot application itself.
+#[tracing::instrument(skip(client, token))]
+pub async fn fetch_application_emojis(
+    client: &reqwest::Client,
+    api_base: &str,
+    token: &str,
+    application_id: u64,
+) -> Result<Vec<ApplicationEmoji>, reqwest::Error> {
+    let response = client
+        .get(format!(
+            "{}/applications/{}/emojis",
+            api_base, application_id
+        ))
+        .header(reqwest::header::AUTHORIZATION, format!("Bot {}", token))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<ApplicationEmojis>()
+        .await?;
+    Ok(response.items)
+}
@@ -0,0 +1,84 @@
+use crate::Data;
+use tomatobot::errors::UpstreamError;
+use tomatobot::Error;
+use tracing::{error, warn};
+
+const ERROR_COLOR: i32 = 0xCD3333;
+
+/// Replaces poise's default handler. Failed and panicked commands get an
+/// embed with a short incident ID, and the full error is logged under the
+/// same ID so a user's report can be found in the logs.
+pub async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
+    match error {
+        poise::FrameworkError::Command { error, ctx } => {
+            let command = &ctx.command().qualified_name;
+            ctx.data().metrics.command_failed(command);
+            let incident = incident_id();
+            error!(
+                incident,
+                command,
+                %error,
+                "Command failed"
+            );
+            let message = match error.downcast_ref::<UpstreamError>() {
+                Some(e) => e.user_message(),
+                None => String::from("Something went wrong on TomatoBot's side"),
+            };
+            report(ctx, &incident, &message).await;
+        }
+        // poise catches the panic, answering here is what keeps the
+        // interaction from being stuck on "thinking"
+        poise::FrameworkError::CommandPanic { payload, ctx } => {
+            let command = &ctx.command().qualified_name;
+            ctx.data().metrics.command_failed(command);
+            let incident = incident_id();
+            error!(
+                incident,
+                command,
+                payload = payload.as_deref().unwrap_or("unknown"),
+                "Command panicked"
+            );
+            report(ctx, &incident, "Something went wrong on TomatoBot's side").await;
+        }
+        poise::FrameworkError::CommandCheckFailed { ctx, error: None }
+            if ctx.data().shutdown.is_requested() =>
+        {
+            let reply = ctx
+                .send(|r| {
+                    r.content("TomatoBot is restarting, try again in a minute")
+                        .ephemeral(true)
+                })
+                .await;
+            if let Err(e) = reply {
+                warn!(error = %e, "Couldn't tell user about the restart");
+            }
+        }
+        error => {
+            if let Err(e) = poise::builtins::on_error(error).await {
+                error!("Error while handling error: {}", e);
+            }
+        }
+    }
+}
+
+// 8 hex characters, short enough for users to copy into a report
+fn incident_id() -> String {
+    format!("{:08x}", rand::random::<u32>())
+}
+
+async fn report(ctx: poise::Context<'_, Data, Error>, incident: &str, message: &str) {
+    let reply = ctx
+        .send(|r| {
+            r.embed(|e| {
+                e.title("Something went wrong")
+                    .description(message)
+                    .color(ERROR_COLOR)
+                    .footer(|f| f.text(format!("Incident {}", incident)))
+            })
+            .ephemeral(true)
+        })
+        .await;
+    if let Err(e) = reply {
+        warn!(incident, error = %e, "Couldn't send error embed");
+    }
+}
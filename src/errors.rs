@@ -1,100 +1,219 @@
-use crate::{serenity, Error};
+use crate::Region;
+use reqwest::StatusCode;
+use std::fmt;
+use std::sync::Arc;
 use thiserror::Error;
 
-#[derive(Debug, Error)]
-pub(crate) enum ExecutionError {
-    #[error("Error during Serenity API Execution")]
-    SerenityApiError(#[from] serenity::Error),
+/// Errors WG reports inside a 200 response, `{"status":"error","error":{...}}`.
+/// The display text is what ends up in front of users.
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum WgApiError {
+    #[error("Invalid search, check the name and try again")]
+    InvalidSearch,
+    #[error("Search is too short, enter at least 3 characters")]
+    NotEnoughSearchLength,
+    #[error("Wargaming is rate limiting, try again")]
+    RequestLimitExceeded,
+    #[error("Wargaming's API is currently unavailable, try again later")]
+    SourceNotAvailable,
+    #[error("The bot's Wargaming application ID is invalid")]
+    InvalidApplicationId,
+    #[error("The bot's Wargaming application is blocked")]
+    ApplicationIsBlocked,
+    #[error("The bot's IP address isn't allowed by Wargaming")]
+    InvalidIpAddress,
+    #[error("Too many {0} in a single Wargaming request")]
+    ListLimitExceeded(String),
+    #[error("Wargaming request is missing {0}")]
+    FieldNotSpecified(String),
+    #[error("Wargaming couldn't find {0}")]
+    FieldNotFound(String),
+    #[error("Invalid {0} sent to Wargaming")]
+    InvalidField(String),
+    #[error("Wargaming API error {code}: {message}")]
+    Other { code: u32, message: String },
 }
 
-#[derive(Debug, Error)]
-pub enum GlobalMapFetchError {
-    //I can think of some global map errors other than this one
-    #[error("Error fetching Global Map data: {0}")]
-    ReqwestResponseError(#[from] Error),
-    #[error("Error parsing Global Map data: {0}")]
-    ParseResponseError(#[from] reqwest::Error),
+/// Which service a request went to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upstream {
+    Tomato,
+    Wargaming,
 }
 
-#[derive(Debug, Error)]
-pub enum ClanRatingFetchError {
-    #[error("Error fetching Clan Rating data: {0}")]
-    ReqwestResponseError(#[from] Error),
-    #[error("Error parsing Clan Rating data: {0}")]
-    ParseResponseError(#[from] reqwest::Error),
+impl Upstream {
+    /// Label for logs and metrics.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Upstream::Tomato => "tomato",
+            Upstream::Wargaming => "wargaming",
+        }
+    }
 }
 
-#[derive(Debug, Error)]
-pub enum RecentTankStatsFetchError {
-    #[error("Error fetching recent tank stats Data: {0}")]
-    ReqwestResponseError(#[from] Error),
-    #[error("Error parsing recent tank stats Data: {0}")]
-    ParseResponseError(#[from] reqwest::Error),
-}
-#[derive(Debug, Error)]
-pub enum TankEconomicsFetchError {
-    #[error("Error fetching Economic Data: {0}")]
-    ReqwestResponseError(#[from] Error),
-    #[error("Error parsing Economic Data: {0}")]
-    ParseResponseError(#[from] reqwest::Error),
+impl fmt::Display for Upstream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Upstream::Tomato => f.write_str("Tomato.gg"),
+            Upstream::Wargaming => f.write_str("Wargaming"),
+        }
+    }
 }
 
-#[derive(Debug, Error)]
-pub enum ClanInfoFetchError {
-    #[error("Error fetching Clan Info: {0}")]
-    ReqwestResponseError(#[from] Error),
-    #[error("Error parsing Clan Info data: {0}")]
-    ParseResponseError(#[from] reqwest::Error),
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpstreamErrorKind {
+    // couldn't connect, or the connection dropped
+    Network,
+    Timeout,
+    // the response didn't match what we expect
+    Decode,
+    NotFound,
+    RateLimited,
+    // any other non-success status, or WG reporting itself unavailable
+    Unavailable,
+    // an error WG reported inside a 200 that isn't covered above
+    Api(WgApiError),
+    // answered, but with nothing worth keeping
+    Empty,
 }
 
-#[derive(Debug, Error)]
-pub enum TomatoClanFetchError {
-    #[error("Error fetching Tomato.gg Clan data: {0}")]
-    ReqwestResponseError(#[from] Error),
-    #[error("Error parsing Tomato.gg Clan data: {0}")]
-    ParseResponseError(#[from] reqwest::Error),
+/// A failed request to Tomato.gg or Wargaming, every fetch returns this.
+/// Cheap to clone, batched WG lookups hand the same error to every waiter.
+#[derive(Debug, Clone)]
+pub struct UpstreamError {
+    pub upstream: Upstream,
+    // ex "overall" or "clans/accountinfo", same as the metrics label
+    pub endpoint: String,
+    pub region: Option<Region>,
+    pub status: Option<StatusCode>,
+    pub kind: UpstreamErrorKind,
+    cause: Option<Arc<dyn std::error::Error + Send + Sync>>,
 }
 
-#[derive(Debug, Error)]
-pub enum FetchAllClanDataError {
-    #[error("Error fetching Global Map data: {0}")]
-    GlobalMapError(#[from] GlobalMapFetchError),
-    #[error("Error fetching Clan Rating data: {0}")]
-    ClanRatingError(#[from] ClanRatingFetchError),
-    #[error("Error fetching Tomato.gg Clan data: {0}")]
-    TomatoClanError(#[from] TomatoClanFetchError),
-}
+impl UpstreamError {
+    pub fn new(upstream: Upstream, endpoint: &str, kind: UpstreamErrorKind) -> UpstreamError {
+        UpstreamError {
+            upstream,
+            endpoint: endpoint.to_owned(),
+            region: None,
+            status: None,
+            kind,
+            cause: None,
+        }
+    }
 
-#[derive(Debug, Error)]
-pub enum FetchClanIDError {
-    #[error("Error getting Clan ID from server: {0}")]
-    GetClanIDError(#[from] reqwest::Error),
-    #[error("Empty response returned from fetching Clan ID")]
-    EmptyResponse,
-}
+    pub fn from_reqwest(
+        upstream: Upstream,
+        endpoint: &str,
+        error: reqwest::Error,
+    ) -> UpstreamError {
+        let kind = if error.is_timeout() {
+            UpstreamErrorKind::Timeout
+        } else if error.is_decode() {
+            UpstreamErrorKind::Decode
+        } else {
+            UpstreamErrorKind::Network
+        };
+        let mut upstream_error = UpstreamError::new(upstream, endpoint, kind);
+        upstream_error.status = error.status();
+        upstream_error.with_cause(error)
+    }
 
-#[derive(Debug, Error)]
-pub enum FetchOverallDataError {
-    #[error("Error fetching Overall Tomato.gg data: {0}")]
-    ReqwestResponseError(#[from] Error),
-    #[error("Error parsing Overall Tomato.gg data: {0}")]
-    ParseResponseError(#[from] reqwest::Error),
-}
+    pub fn from_status(upstream: Upstream, endpoint: &str, status: StatusCode) -> UpstreamError {
+        let kind = match status {
+            StatusCode::NOT_FOUND => UpstreamErrorKind::NotFound,
+            StatusCode::TOO_MANY_REQUESTS => UpstreamErrorKind::RateLimited,
+            _ => UpstreamErrorKind::Unavailable,
+        };
+        let mut upstream_error = UpstreamError::new(upstream, endpoint, kind);
+        upstream_error.status = Some(status);
+        upstream_error
+    }
 
-#[derive(Debug, Error)]
-pub enum FetchRecentsDataError {
-    #[error("Error fetching Recents Tomato.gg data: {0}")]
-    ReqwestResponseError(#[from] Error),
-    #[error("Error parsing Recents Tomato.gg data: {0}")]
-    ParseResponseError(#[from] reqwest::Error),
+    pub fn decode(
+        upstream: Upstream,
+        endpoint: &str,
+        error: impl std::error::Error + Send + Sync + 'static,
+    ) -> UpstreamError {
+        UpstreamError::new(upstream, endpoint, UpstreamErrorKind::Decode).with_cause(error)
+    }
+
+    pub fn wargaming(endpoint: &str, error: WgApiError) -> UpstreamError {
+        let kind = match error {
+            WgApiError::RequestLimitExceeded => UpstreamErrorKind::RateLimited,
+            WgApiError::SourceNotAvailable => UpstreamErrorKind::Unavailable,
+            error => UpstreamErrorKind::Api(error),
+        };
+        UpstreamError::new(Upstream::Wargaming, endpoint, kind)
+    }
+
+    pub fn in_region(mut self, region: Region) -> UpstreamError {
+        self.region = Some(region);
+        self
+    }
+
+    fn with_cause(
+        mut self,
+        cause: impl std::error::Error + Send + Sync + 'static,
+    ) -> UpstreamError {
+        self.cause = Some(Arc::new(cause));
+        self
+    }
+
+    /// What to tell the user, the details stay in the logs.
+    pub fn user_message(&self) -> String {
+        match &self.kind {
+            UpstreamErrorKind::Network | UpstreamErrorKind::Unavailable => format!(
+                "{} is currently unavailable, try again later",
+                self.upstream
+            ),
+            UpstreamErrorKind::Timeout => {
+                format!("{} took too long to answer, try again", self.upstream)
+            }
+            UpstreamErrorKind::Decode => format!(
+                "{} sent something TomatoBot couldn't read, try again later",
+                self.upstream
+            ),
+            UpstreamErrorKind::NotFound => format!("{} has nothing on that", self.upstream),
+            UpstreamErrorKind::RateLimited => {
+                format!("{} is rate limiting, try again in a bit", self.upstream)
+            }
+            UpstreamErrorKind::Api(error) => error.to_string(),
+            UpstreamErrorKind::Empty => {
+                format!("{} has no data right now, try again later", self.upstream)
+            }
+        }
+    }
 }
 
-#[derive(Debug, Error)]
-pub enum FetchUserIDError {
-    #[error("Error getting User ID from server: {0}")]
-    ReqwestResponseError(#[from] reqwest::Error),
+impl fmt::Display for UpstreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.upstream, self.endpoint)?;
+        if let Some(region) = self.region {
+            write!(f, " ({})", region.name())?;
+        }
+        match &self.kind {
+            UpstreamErrorKind::Network => f.write_str(" network error")?,
+            UpstreamErrorKind::Timeout => f.write_str(" timed out")?,
+            UpstreamErrorKind::Decode => f.write_str(" decode error")?,
+            UpstreamErrorKind::NotFound => f.write_str(" not found")?,
+            UpstreamErrorKind::RateLimited => f.write_str(" rate limited")?,
+            UpstreamErrorKind::Unavailable => f.write_str(" unavailable")?,
+            UpstreamErrorKind::Api(error) => write!(f, " error: {}", error)?,
+            UpstreamErrorKind::Empty => f.write_str(" returned no data")?,
+        }
+        if let Some(status) = self.status {
+            write!(f, " [{}]", status)?;
+        }
+        if let Some(cause) = &self.cause {
+            write!(f, ": {}", cause)?;
+        }
+        Ok(())
+    }
 }
 
+impl std::error::Error for UpstreamError {}
+
 #[derive(Debug, Error)]
 pub enum CreatePeriodEmbedError {
     #[error("Missing Required Recents Data")]
@@ -108,3 +227,31 @@ pub enum CreateMainStatEmbedError {
     #[error("Missing Required Overall Data")]
     MissingOverallError,
 }
+
+#[derive(Debug, Error)]
+pub enum CreateCompareEmbedError {
+    #[error("Missing Required Overall Data for {0}")]
+    MissingOverallError(String),
+    #[error("Missing Required Recents Data for {0}")]
+    MissingRecentsError(String),
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("Error reading config file: {0}")]
+    ReadError(#[from] std::io::Error),
+    #[error("Error parsing config file: {0}")]
+    ParseError(#[from] toml::de::Error),
+}
+
+#[derive(Debug, Error)]
+pub enum StoreError {
+    #[error("Error accessing the store: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Error (de)serializing stored data: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("Store task failed: {0}")]
+    Task(#[from] tokio::task::JoinError),
+    #[error("Unknown region {0} in the store")]
+    UnknownRegion(String),
+}
@@ -0,0 +1,94 @@
+//! Everything TomatoBot knows about World of Tanks stats without the Discord
+//! side: the Tomato.gg and Wargaming clients, the domain types, the cached
+//! tank datasets and the embed builders. [`StatsService`] is the entry point.
+
+pub mod batch;
+pub mod clans;
+pub mod client;
+pub mod config;
+pub mod embed;
+pub mod emoji;
+pub mod errors;
+pub mod metrics;
+pub mod player_stats;
+pub mod ratelimit;
+pub mod scheduler;
+pub mod service;
+pub mod store;
+pub mod tanks;
+pub mod telemetry;
+pub mod vehicle;
+pub mod wargaming;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+pub use service::StatsService;
+
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(
+    Debug,
+    EnumIter,
+    PartialEq,
+    Eq,
+    Hash,
+    poise::ChoiceParameter,
+    Clone,
+    Copy,
+    Default,
+    Serialize,
+    Deserialize,
+)]
+pub enum Region {
+    #[default]
+    NA,
+    EU,
+    ASIA,
+}
+
+impl Region {
+    pub fn extension(&self) -> &str {
+        match self {
+            Region::NA => "com",
+            Region::EU => "eu",
+            Region::ASIA => "asia",
+        }
+    }
+}
+
+pub fn get_short_position(position: &str) -> &str {
+    match position {
+        "commander" => "CDR",
+        "executive_officer" => "XO",
+        "personnel_officer" => "PO",
+        "combat_officer" => "CO",
+        "recruitment_officer" => "RO",
+        "intelligence_officer" => "IO",
+        "quartermaster" => "QM",
+        "junior_officer" => "JO",
+        "private" => "PVT",
+        "recruit" => "RCT",
+        "reservist" => "RES",
+        _ => "Err",
+    }
+}
+
+pub fn get_wn8_color(wn8: u32) -> i32 {
+    match wn8 {
+        0 => 0x808080,
+        1..=300 => 0x930D0D,
+        301..=450 => 0xCD3333,
+        451..=650 => 0xCC7A00,
+        651..=900 => 0xCCB800,
+        901..=1200 => 0x849B24,
+        1201..=1600 => 0x4D7326,
+        1601..=2000 => 0x4099BF,
+        2001..=2450 => 0x3972C6,
+        2451..=2900 => 0x6844d4,
+        2901..=3400 => 0x522b99,
+        3401..=4000 => 0x411d73,
+        4001..=4700 => 0x310d59,
+        4701..=u32::MAX => 0x24073d,
+    }
+}
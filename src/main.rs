@@ -1,140 +1,198 @@
 mod commands;
-mod errors;
-mod player_stats;
+mod error_handler;
+mod server;
+mod shutdown;
 use commands::clanstats::clanstats;
-use commands::marks::{RecentTankStats,fetch_tank_economics, generate_tank_map, marks, Tank, TankEconomics, generate_recent_tank_map};
-use commands::stats::stats;
+use commands::compare::compare;
+use commands::link::{link, unlink, whoami};
+use commands::marks::marks;
+use commands::stats::{stats, view_stats};
 use poise::serenity_prelude as serenity;
-use std::collections::HashMap;
+use server::ServerState;
+use shutdown::Shutdown;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use strum_macros::EnumIter;
-use tokio::sync::Mutex;
-use tokio::time;
-
-#[derive(Debug, EnumIter, PartialEq, Eq, Hash, poise::ChoiceParameter, Clone, Copy, Default)]
-pub enum Region {
-    #[default]
-    NA,
-    EU,
-    ASIA,
-}
-
-impl Region {
-    fn extension(&self) -> &str {
-        match self {
-            Region::NA => "com",
-            Region::EU => "eu",
-            Region::ASIA => "asia",
-        }
-    }
-}
+use tomatobot::client::{TomatoClient, UpstreamClient, WargamingClient};
+use tomatobot::config::Config;
+use tomatobot::emoji::{fetch_application_emojis, Emojis, DISCORD_API_BASE};
+use tomatobot::metrics::Metrics;
+use tomatobot::scheduler::Scheduler;
+use tomatobot::service::TankCaches;
+use tomatobot::store::Store;
+use tomatobot::{telemetry, Error, StatsService};
+use tracing::{error, info, warn};
 
 pub struct Data {
-    tank_data: Arc<Mutex<HashMap<Region, Vec<Tank>>>>,
-    tank_economics: Arc<Mutex<Vec<TankEconomics>>>,
-    recent_tank_stats: Arc<Mutex<HashMap<Region, Vec<RecentTankStats>>>>,
+    service: StatsService,
+    emojis: Emojis,
+    store: Store,
+    metrics: Metrics,
+    gateway_connected: Arc<AtomicBool>,
+    shutdown: Shutdown,
 }
 
-type Error = Box<dyn std::error::Error + Send + Sync>;
 type Context<'a> = poise::Context<'a, Data, Error>;
 
-pub fn get_short_position(position: &str) -> &str {
-    match position {
-        "commander" => "CDR",
-        "executive_officer" => "XO",
-        "personnel_officer" => "PO",
-        "combat_officer" => "CO",
-        "recruitment_officer" => "RO",
-        "intelligence_officer" => "IO",
-        "quartermaster" => "QM",
-        "junior_officer" => "JO",
-        "private" => "PVT",
-        "recruit" => "RCT",
-        "reservist" => "RES",
-        _ => "Err",
+// drives /healthz, any shard stage other than Connected counts as down
+fn track_gateway(event: &poise::Event<'_>, connected: &AtomicBool) {
+    match event {
+        poise::Event::Ready { .. } | poise::Event::Resume { .. } => {
+            connected.store(true, Ordering::Relaxed)
+        }
+        // poise's prelude can't name ConnectionStage, two of its globs both
+        // bring in a `gateway` module, so go by its Display instead
+        poise::Event::ShardStageUpdate { update } => {
+            connected.store(update.new.to_string() == "connected", Ordering::Relaxed)
+        }
+        _ => {}
     }
 }
 
-pub fn get_wn8_color(wn8: u32) -> i32 {
-    match wn8 {
-        0 => 0x808080,
-        1..=300 => 0x930D0D,
-        301..=450 => 0xCD3333,
-        451..=650 => 0xCC7A00,
-        651..=900 => 0xCCB800,
-        901..=1200 => 0x849B24,
-        1201..=1600 => 0x4D7326,
-        1601..=2000 => 0x4099BF,
-        2001..=2450 => 0x3972C6,
-        2451..=2900 => 0x6844d4,
-        2901..=3400 => 0x522b99,
-        3401..=4000 => 0x411d73,
-        4001..=4700 => 0x310d59,
-        4701..=u32::MAX => 0x24073d,
-    }
-}
+#[tokio::main]
+async fn main() {
+    let config = Config::load().expect("failed to load config");
+    telemetry::init(&config.logging);
+    let metrics = Metrics::new();
+    let http =
+        UpstreamClient::new(&config.http, metrics.clone()).expect("failed to build http client");
+    let store = Store::open(&config.store.path).expect("failed to open store");
 
-async fn update_recent_tank_data(data: Arc<Mutex<HashMap<Region, Vec<RecentTankStats>>>>) {
-    let mut interval = time::interval(Duration::from_secs(36000000));
-    loop {
-        interval.tick().await;
-        let mut old = data.lock().await;
-        *old = generate_recent_tank_map().await;
-    }
-}
+    let tomato = TomatoClient::new(http.clone(), &config);
+    let scheduler = Scheduler::new(tomato.clone(), store.clone(), config.scheduler.clone());
 
-async fn update_tank_data(data: Arc<Mutex<HashMap<Region, Vec<Tank>>>>) {
-    let mut interval = time::interval(Duration::from_secs(36000));
-    loop {
-        interval.tick().await;
-        let mut old = data.lock().await;
-        *old = generate_tank_map().await;
-    }
-}
+    // serve whatever was saved last run right away, the scheduler replaces
+    // it in the background
+    let caches = TankCaches::load(&store, &scheduler.status()).await;
+    scheduler.start(&caches);
 
-async fn update_tank_economics(economics: Arc<Mutex<Vec<TankEconomics>>>) {
-    let mut interval = time::interval(Duration::from_secs(128000));
-    loop {
-        interval.tick().await;
-        let mut old = economics.lock().await;
-        match fetch_tank_economics().await {
-            Ok(tanks) => {
-                *old = tanks;
-            }
-            Err(e) => {
-                println!("Error in tank economics: {}", e);
+    let data = Data {
+        service: StatsService::new(
+            tomato,
+            WargamingClient::new(http, &config),
+            caches.clone(),
+            scheduler.status(),
+        ),
+        emojis: Emojis::from_config(&config.emojis),
+        store: store.clone(),
+        metrics: metrics.clone(),
+        gateway_connected: Arc::new(AtomicBool::new(false)),
+        shutdown: Shutdown::new(),
+    };
+
+    let shutdown = data.shutdown.clone();
+
+    if config.server.enabled {
+        let state = ServerState {
+            metrics,
+            refresh_status: scheduler.status(),
+            gateway_connected: Arc::clone(&data.gateway_connected),
+            caches: caches.clone(),
+        };
+        let bind = config.server.bind;
+        tokio::spawn(async move {
+            if let Err(e) = server::serve(bind, state).await {
+                error!("HTTP server stopped: {}", e);
             }
-        }
+        });
     }
-}
 
-#[tokio::main]
-async fn main() {
-    let data = Data {
-        tank_data: Arc::new(Mutex::new(HashMap::new())),
-        tank_economics: Arc::new(Mutex::new(Vec::new())),
-        recent_tank_stats: Arc::new(Mutex::new(HashMap::new())),
-    };
-    tokio::spawn(update_tank_data(Arc::clone(&data.tank_data)));
-    tokio::spawn(update_tank_economics(Arc::clone(&data.tank_economics)));
-    tokio::spawn(update_recent_tank_data(Arc::clone(&data.recent_tank_stats)));
+    let token = std::env::var("DISCORD_TOKEN").expect("missing DISCORD_TOKEN");
+    let discover_emojis = config.emojis.discover;
+    // setup doesn't return until discovery does, a stalled Discord API
+    // mustn't keep the bot from coming up
+    let discord_http = reqwest::Client::builder()
+        .timeout(Duration::from_secs(config.http.timeout_secs))
+        .connect_timeout(Duration::from_secs(config.http.connect_timeout_secs))
+        .build()
+        .expect("failed to build discord http client");
 
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
-            commands: vec![marks(), stats(), clanstats()],
+            commands: vec![
+                marks(),
+                stats(),
+                view_stats(),
+                clanstats(),
+                compare(),
+                link(),
+                unlink(),
+                whoami(),
+            ],
+            pre_command: |ctx| {
+                Box::pin(async move {
+                    ctx.data()
+                        .metrics
+                        .command_invoked(&ctx.command().qualified_name);
+                })
+            },
+            command_check: Some(|ctx| {
+                Box::pin(async move { Ok(!ctx.data().shutdown.is_requested()) })
+            }),
+            on_error: |error| Box::pin(error_handler::on_error(error)),
+            event_handler: |_ctx, event, _framework, data| {
+                Box::pin(async move {
+                    track_gateway(event, &data.gateway_connected);
+                    Ok(())
+                })
+            },
             ..Default::default()
         })
-        .token(std::env::var("DISCORD_TOKEN").expect("missing DISCORD_TOKEN"))
+        .token(token.clone())
         .intents(serenity::GatewayIntents::non_privileged())
-        .setup(|ctx, _ready, framework| {
+        .setup(move |ctx, ready, framework| {
             Box::pin(async move {
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
+                let mut data = data;
+                if discover_emojis {
+                    // not worth failing startup over, the embeds fall back
+                    // to text
+                    match fetch_application_emojis(
+                        &discord_http,
+                        DISCORD_API_BASE,
+                        &token,
+                        ready.application.id.0,
+                    )
+                    .await
+                    {
+                        Ok(emojis) => data.emojis.discover(emojis),
+                        Err(e) => warn!("Couldn't fetch application emojis: {}", e),
+                    }
+                }
                 Ok(data)
             })
         });
 
-    if let Err(e) = framework.run().await {
-        eprintln!("{}", e);
+    let framework = match framework.build().await {
+        Ok(framework) => framework,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    // SIGTERM: refuse new commands, let open /stats messages strip their
+    // components, then take the shards down. The scheduler saves each
+    // dataset after every refresh, so the caches have nothing left to save
+    let shard_manager = Arc::clone(framework.shard_manager());
+    let timeout = Duration::from_secs(config.shutdown.timeout_secs);
+    tokio::spawn(async move {
+        shutdown::signal().await;
+        info!("Shutting down");
+        shutdown.request();
+
+        if tokio::time::timeout(timeout, shutdown.drained())
+            .await
+            .is_err()
+        {
+            warn!(?timeout, "Shutdown cleanup didn't finish in time");
+        }
+
+        shard_manager.lock().await.shutdown_all().await;
+    });
+
+    if let Err(e) = framework.start().await {
+        error!("{}", e);
     }
+    info!("Stopped");
 }
@@ -0,0 +1,165 @@
+use crate::scheduler::SchedulerStatus;
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry,
+    TextEncoder,
+};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// Everything exported on `/metrics`. Cheap to clone, all the collectors
+/// are shared.
+#[derive(Clone)]
+pub struct Metrics {
+    inner: Arc<MetricsInner>,
+}
+
+struct MetricsInner {
+    registry: Registry,
+    command_invocations: IntCounterVec,
+    command_failures: IntCounterVec,
+    upstream_latency: HistogramVec,
+    active_collectors: IntGauge,
+    dataset_age: GaugeVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let registry = Registry::new_custom(Some(String::from("tomatobot")), None)
+            .expect("valid metrics prefix");
+
+        let command_invocations = IntCounterVec::new(
+            Opts::new("command_invocations_total", "Slash commands invoked"),
+            &["command"],
+        )
+        .unwrap();
+        let command_failures = IntCounterVec::new(
+            Opts::new(
+                "command_failures_total",
+                "Slash commands that returned an error",
+            ),
+            &["command"],
+        )
+        .unwrap();
+        let upstream_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "upstream_request_duration_seconds",
+                "Latency of Tomato.gg and Wargaming requests, retries included",
+            )
+            .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0]),
+            &["upstream", "endpoint", "status"],
+        )
+        .unwrap();
+        let active_collectors = IntGauge::new(
+            "active_component_collectors",
+            "/stats messages currently listening for component interactions",
+        )
+        .unwrap();
+        let dataset_age = GaugeVec::new(
+            Opts::new(
+                "dataset_age_seconds",
+                "Seconds since each cached dataset was last refreshed",
+            ),
+            &["dataset", "region"],
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(command_invocations.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(command_failures.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(upstream_latency.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(active_collectors.clone()))
+            .unwrap();
+        registry.register(Box::new(dataset_age.clone())).unwrap();
+
+        Metrics {
+            inner: Arc::new(MetricsInner {
+                registry,
+                command_invocations,
+                command_failures,
+                upstream_latency,
+                active_collectors,
+                dataset_age,
+            }),
+        }
+    }
+
+    pub fn command_invoked(&self, command: &str) {
+        self.inner
+            .command_invocations
+            .with_label_values(&[command])
+            .inc();
+    }
+
+    pub fn command_failed(&self, command: &str) {
+        self.inner
+            .command_failures
+            .with_label_values(&[command])
+            .inc();
+    }
+
+    // status is "error" when the request never got a response
+    pub fn observe_upstream(
+        &self,
+        upstream: &str,
+        endpoint: &str,
+        status: &str,
+        latency: Duration,
+    ) {
+        self.inner
+            .upstream_latency
+            .with_label_values(&[upstream, endpoint, status])
+            .observe(latency.as_secs_f64());
+    }
+
+    /// Counts a component collector as active until the guard is dropped.
+    pub fn collector_guard(&self) -> CollectorGuard {
+        self.inner.active_collectors.inc();
+        CollectorGuard {
+            gauge: self.inner.active_collectors.clone(),
+        }
+    }
+
+    /// Renders the text exposition format, dataset ages are computed at
+    /// scrape time from the scheduler's status.
+    pub fn render(&self, refresh_status: &SchedulerStatus) -> String {
+        let now = SystemTime::now();
+        for ((dataset, region), status) in refresh_status.all() {
+            if let Some(last_success) = status.last_success {
+                let age = now.duration_since(last_success).unwrap_or_default();
+                let region = region.map(|r| format!("{:?}", r)).unwrap_or_default();
+                self.inner
+                    .dataset_age
+                    .with_label_values(&[dataset.key(), &region])
+                    .set(age.as_secs_f64());
+            }
+        }
+
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.inner.registry.gather(), &mut buffer)
+            .unwrap();
+        String::from_utf8(buffer).unwrap()
+    }
+}
+
+pub struct CollectorGuard {
+    gauge: IntGauge,
+}
+
+impl Drop for CollectorGuard {
+    fn drop(&mut self) {
+        self.gauge.dec();
+    }
+}
@@ -0,0 +1,146 @@
+use crate::errors::CreateCompareEmbedError;
+use crate::get_wn8_color;
+use crate::player_stats::player::{Period, PlayerData};
+use crate::player_stats::recents::TankStats;
+use poise::serenity_prelude::CreateEmbed;
+use std::fmt::Display;
+
+const SHARED_TANKS_SHOWN: usize = 10;
+
+// the stats both sides of a section have, lifetime or a period
+struct Totals {
+    battles: u32,
+    wn8: u32,
+    win_rate: f32,
+    tier: f32,
+    dpg: u32,
+}
+
+// the higher value gets bolded, ties stay plain
+fn highlight<T: PartialOrd + Display>(value: T, other: T) -> String {
+    match value > other {
+        true => format!("**{}**", value),
+        false => value.to_string(),
+    }
+}
+
+fn column(totals: &Totals, other: &Totals) -> String {
+    format!(
+        "{}\n{}\n{}%\n{}\n{}",
+        highlight(totals.battles, other.battles),
+        highlight(totals.wn8, other.wn8),
+        highlight(totals.win_rate, other.win_rate),
+        highlight(totals.tier, other.tier),
+        highlight(totals.dpg, other.dpg)
+    )
+}
+
+// three inline fields make one row: the labels, then each player's column
+fn add_section(embed: &mut CreateEmbed, title: &str, players: [(&str, &Totals); 2]) {
+    let [(a_name, a), (b_name, b)] = players;
+    embed
+        .field(title, "Battles\nWN8\nWinRate\nAvgTier\nDPG", true)
+        .field(a_name, column(a, b), true)
+        .field(b_name, column(b, a), true);
+}
+
+/// Tanks in both lists, most played between the two first.
+fn shared_tanks<'a>(a: &'a [TankStats], b: &'a [TankStats]) -> Vec<(&'a TankStats, &'a TankStats)> {
+    let mut shared: Vec<(&TankStats, &TankStats)> = a
+        .iter()
+        .filter_map(|a_tank| {
+            b.iter()
+                .find(|b_tank| b_tank.id == a_tank.id)
+                .map(|b_tank| (a_tank, b_tank))
+        })
+        .collect();
+    shared.sort_by_key(|(a_tank, b_tank)| std::cmp::Reverse(a_tank.battles + b_tank.battles));
+    shared
+}
+
+pub async fn generate_compare_embed(
+    a: &PlayerData,
+    b: &PlayerData,
+    period: Period,
+) -> Result<CreateEmbed, CreateCompareEmbedError> {
+    let mut overall = Vec::with_capacity(2);
+    let mut recent = Vec::with_capacity(2);
+    for data in [a, b] {
+        let nickname = &data.player.nickname;
+        let Some(stats) = data.overall.found() else {
+            return Err(CreateCompareEmbedError::MissingOverallError(
+                nickname.clone(),
+            ));
+        };
+        let Some(time_frame) = data.get_period_data(period) else {
+            return Err(CreateCompareEmbedError::MissingRecentsError(
+                nickname.clone(),
+            ));
+        };
+        overall.push(Totals {
+            battles: stats.battles,
+            wn8: stats.wn8,
+            win_rate: stats.win_rate,
+            tier: stats.tier,
+            dpg: stats.dpg,
+        });
+        recent.push((
+            Totals {
+                battles: time_frame.overall.battles,
+                wn8: time_frame.overall.wn8,
+                win_rate: time_frame.overall.winrate,
+                tier: time_frame.overall.tier,
+                dpg: time_frame.overall.dpg,
+            },
+            &time_frame.tank_stats,
+        ));
+    }
+
+    let (a_name, b_name) = (a.player.nickname.as_str(), b.player.nickname.as_str());
+    let mut embed = CreateEmbed::default();
+    embed.title(format!("{} vs {}", a_name, b_name));
+    add_section(
+        &mut embed,
+        "**Overall**",
+        [(a_name, &overall[0]), (b_name, &overall[1])],
+    );
+    add_section(
+        &mut embed,
+        &format!("**{}**", period.nice_name()),
+        [(a_name, &recent[0].0), (b_name, &recent[1].0)],
+    );
+
+    let shared = shared_tanks(recent[0].1, recent[1].1);
+    let tank_lines: Vec<String> = shared
+        .iter()
+        .take(SHARED_TANKS_SHOWN)
+        .map(|(a_tank, b_tank)| {
+            format!(
+                "{}: {} vs {} WN8, {} / {} battles",
+                a_tank.name,
+                highlight(a_tank.wn8, b_tank.wn8),
+                highlight(b_tank.wn8, a_tank.wn8),
+                a_tank.battles,
+                b_tank.battles
+            )
+        })
+        .collect();
+    let tanks = match tank_lines.is_empty() {
+        true => String::from("No tanks in common"),
+        false => tank_lines.join("\n"),
+    };
+    embed.field(
+        format!("Tanks Both Played ({})", period.nice_name()),
+        tanks,
+        false,
+    );
+
+    embed.footer(|f| {
+        f.text("Powered by Tomato.gg");
+        f.icon_url("https://tomato.gg/_next/image?url=%2Ftomato.png&w=48&q=75");
+        f
+    });
+    // whoever did better over the period sets the colour
+    embed.color(get_wn8_color(recent[0].0.wn8.max(recent[1].0.wn8)));
+    Ok(embed)
+}
@@ -1,2 +1,99 @@
+pub mod compare;
 pub mod overall;
+pub mod player;
 pub mod recents;
+
+use crate::client::TomatoClient;
+use crate::errors::{UpstreamError, UpstreamErrorKind};
+use crate::Region;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+/// What Tomato.gg had for a player. Only `Found` has stats, the rest say
+/// why there aren't any so /stats can tell the user.
+#[derive(Debug, Clone)]
+pub enum TomatoStats<T> {
+    Found(T),
+    // Tomato has never seen the player
+    NotTracked,
+    Private,
+    // Tomato answered, but not in the shape we expect
+    SchemaMismatch(UpstreamError),
+    Unavailable(UpstreamError),
+}
+
+impl<T> TomatoStats<T> {
+    pub fn found(&self) -> Option<&T> {
+        match self {
+            TomatoStats::Found(stats) => Some(stats),
+            _ => None,
+        }
+    }
+
+    /// Why there's nothing to show, None when the stats were found.
+    pub fn user_message(&self) -> Option<String> {
+        match self {
+            TomatoStats::Found(_) => None,
+            TomatoStats::NotTracked => Some(String::from("Player isn't tracked on Tomato.gg yet")),
+            TomatoStats::Private => Some(String::from("Player's stats are private")),
+            TomatoStats::SchemaMismatch(_) => Some(String::from(
+                "Tomato.gg changed how it sends stats, TomatoBot needs an update",
+            )),
+            TomatoStats::Unavailable(e) => Some(e.user_message()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct TomatoResponse<T> {
+    #[serde(default)]
+    meta: Option<Meta>,
+    // null when the player isn't tracked
+    data: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct Meta {
+    #[serde(default)]
+    message: Option<String>,
+}
+
+/// GETs one of Tomato's per player endpoints and sorts the answer into a
+/// [`TomatoStats`]. Schema mismatches and failures get logged here.
+async fn fetch_tomato_stats<T: DeserializeOwned>(
+    client: &TomatoClient,
+    url: &str,
+    region: &Region,
+) -> TomatoStats<T> {
+    match client
+        .get_json::<TomatoResponse<T>>(url, Some(*region))
+        .await
+    {
+        Ok(TomatoResponse {
+            data: Some(stats), ..
+        }) => TomatoStats::Found(stats),
+        Ok(TomatoResponse { meta, data: None }) => {
+            let message = meta.and_then(|meta| meta.message).unwrap_or_default();
+            debug!(message, "No stats on Tomato.gg");
+            let message = message.to_lowercase();
+            if message.contains("private") || message.contains("hidden") {
+                TomatoStats::Private
+            } else {
+                TomatoStats::NotTracked
+            }
+        }
+        Err(e) if e.kind == UpstreamErrorKind::NotFound => TomatoStats::NotTracked,
+        Err(e) if e.status == Some(StatusCode::FORBIDDEN) => TomatoStats::Private,
+        Err(e) if e.kind == UpstreamErrorKind::Decode => {
+            // the error carries the path into the payload, ex "data.battles"
+            warn!(error = %e, "Tomato.gg stats didn't match the expected schema");
+            TomatoStats::SchemaMismatch(e)
+        }
+        Err(e) => {
+            warn!(error = %e, "Error fetching Tomato.gg stats");
+            TomatoStats::Unavailable(e)
+        }
+    }
+}
@@ -1,13 +1,9 @@
-use crate::commands::stats::Player;
-use crate::errors::FetchOverallDataError;
+use crate::client::TomatoClient;
+use crate::player_stats::player::Player;
+use crate::player_stats::{fetch_tomato_stats, TomatoStats};
 use crate::Region;
 use serde::Deserialize;
 
-#[derive(Deserialize)]
-struct OverallResponse {
-    data: OverallData,
-}
-
 #[derive(Deserialize, Clone, Default)]
 pub struct OverallData {
     pub server: String,
@@ -22,28 +18,23 @@ pub struct OverallData {
     pub dpg: u32,
 }
 
+#[tracing::instrument(skip(client, user), fields(account_id = user.account_id))]
 pub async fn fetch_overall_data(
+    client: &TomatoClient,
     region: &Region,
     user: &Player,
     cached: bool,
-) -> Result<Option<OverallData>, FetchOverallDataError> {
+) -> TomatoStats<OverallData> {
     let url = format!(
-        "{}{}",
-        format!(
-            "https://api.tomato.gg/dev/api-v2/overall/{}/{}",
-            region.extension(),
-            user.account_id
-        ),
+        "{}/overall/{}/{}{}",
+        client.api_base(),
+        region.extension(),
+        user.account_id,
         match cached {
             true => "?cache=true",
             false => "",
         }
     );
 
-    let parsed_data = reqwest::get(url).await?.json::<OverallResponse>().await;
-
-    match parsed_data {
-        Ok(data) => Ok(Some(data.data)),
-        Err(_) => Ok(None),
-    }
+    fetch_tomato_stats(client, &url, region).await
 }
@@ -0,0 +1,487 @@
+use crate::clans::ClanData;
+use crate::client::WargamingClient;
+use crate::errors::{CreateMainStatEmbedError, CreatePeriodEmbedError, Upstream, UpstreamError};
+use crate::player_stats::{
+    overall::OverallData, recents::RecentsData, recents::TimeFrame, TomatoStats,
+};
+use crate::{get_short_position, get_wn8_color, Region};
+use poise::serenity_prelude::CreateEmbed;
+use serde::Deserialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+use tokio::join;
+use tracing::warn;
+
+//TODO rename this so that its clear its for clan data
+#[derive(Deserialize, Clone)]
+pub struct PlayerAccountInfo {
+    pub clan: PlayerClanInfo,
+    pub role: String,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct PlayerClanInfo {
+    pub tag: String,
+    pub emblems: Emblems,
+    pub clan_id: u32,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct Emblems {
+    pub x64: EmblemURL,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct EmblemURL {
+    pub portal: String,
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct Player {
+    pub nickname: String,
+    pub account_id: u32,
+}
+
+pub struct PlayerData {
+    pub player_clan: Option<PlayerAccountInfo>,
+    pub clan: Option<ClanData>,
+    pub player: Player,
+    pub region: Region,
+    pub overall: TomatoStats<OverallData>,
+    pub recents: TomatoStats<RecentsData>,
+    pub is_in_clan: bool,
+}
+
+impl PlayerData {
+    /// Why the period's stats, or the main embed's with no period, can't be
+    /// shown. None when they can.
+    pub fn missing_stats_message(&self, period: Option<Period>) -> Option<String> {
+        match period {
+            Some(_) => self.recents.user_message(),
+            None => self
+                .overall
+                .user_message()
+                .or_else(|| self.recents.user_message()),
+        }
+    }
+
+    pub fn get_period_data(&self, period: Period) -> Option<&TimeFrame> {
+        if let Some(recents) = self.recents.found() {
+            match period {
+                Period::R24HR => {
+                    return Some(&recents.recent24hr);
+                }
+                Period::R3DAYS => {
+                    return Some(&recents.recent3days);
+                }
+                Period::R7DAYS => {
+                    return Some(&recents.recent7days);
+                }
+                Period::R30DAYS => {
+                    return Some(&recents.recent30days);
+                }
+                Period::R60DAYS => {
+                    return Some(&recents.recent60days);
+                }
+                Period::R1000BATTLES => {
+                    return Some(&recents.recent1000battles);
+                }
+                Period::R100BATTLES => {
+                    return Some(&recents.recent100battles);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[derive(poise::ChoiceParameter, Clone, Copy, EnumIter)]
+pub enum Period {
+    R24HR,
+    R3DAYS,
+    R7DAYS,
+    R30DAYS,
+    R60DAYS,
+    R1000BATTLES,
+    R100BATTLES,
+}
+
+impl Period {
+    pub fn nice_name(&self) -> &str {
+        match self {
+            Period::R24HR => "24 Hours",
+            Period::R3DAYS => "3 Days",
+            Period::R7DAYS => "7 Days",
+            Period::R30DAYS => "30 Days",
+            Period::R60DAYS => "60 Days",
+            Period::R1000BATTLES => "1000 Battles",
+            Period::R100BATTLES => "100 Battles",
+        }
+    }
+}
+
+/// Nicknames only ever have letters, digits and underscores, anything else
+/// can't match and would end up in the query string as is.
+pub fn valid_nickname(input: &str) -> bool {
+    input.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Autocomplete answers with `nickname:REGION` so the region a suggestion
+/// was found in carries over to the command. Nicknames can't have a colon.
+pub fn suggestion_value(nickname: &str, region: Region) -> String {
+    format!("{}:{}", nickname, region.name())
+}
+
+/// Splits a [`suggestion_value`] back up. A name typed out by hand comes
+/// back as is, with no region.
+pub fn parse_suggestion(value: &str) -> (&str, Option<Region>) {
+    let Some((nickname, region)) = value.rsplit_once(':') else {
+        return (value, None);
+    };
+    match Region::iter().find(|known| known.name() == region) {
+        Some(region) => (nickname, Some(region)),
+        None => (value, None),
+    }
+}
+
+/// The player called exactly `input` (ignoring case), if there is one.
+#[tracing::instrument(skip(client))]
+pub async fn fetch_user_id(
+    client: &WargamingClient,
+    input: &str,
+    region: Region,
+) -> Result<Option<Player>, UpstreamError> {
+    if input.is_empty() || !valid_nickname(input) {
+        return Ok(None);
+    }
+
+    let wot_user_url = format!(
+        "{}/wot/account/list/?language=en&application_id={}&search={}&type=exact",
+        client.host(&region),
+        client.account_application_id(),
+        input
+    );
+
+    let response = client
+        .get_json::<Vec<Player>>(&wot_user_url, region)
+        .await?;
+
+    // a prefix search would hand back "tomato_enjoyer2" for "tomato_enjoyer"
+    Ok(response
+        .into_iter()
+        .find(|player| player.nickname.eq_ignore_ascii_case(input)))
+}
+
+/// Up to `limit` players whose nickname starts with `input`.
+#[tracing::instrument(skip(client))]
+pub async fn search_players(
+    client: &WargamingClient,
+    input: &str,
+    region: Region,
+    limit: usize,
+) -> Result<Vec<Player>, UpstreamError> {
+    let wot_user_url = format!(
+        "{}/wot/account/list/?language=en&application_id={}&search={}&limit={}",
+        client.host(&region),
+        client.account_application_id(),
+        input,
+        limit
+    );
+
+    client.get_json::<Vec<Player>>(&wot_user_url, region).await
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn fetch_clan_info(
+    client: &WargamingClient,
+    region: &Region,
+    account_id: &u32,
+) -> Result<Option<PlayerAccountInfo>, UpstreamError> {
+    let response = client
+        .get_account_batched(
+            "clans/accountinfo",
+            client.clan_application_id(),
+            region,
+            *account_id,
+        )
+        .await?;
+
+    match response {
+        Some(output) => serde_json::from_value(output).map_err(|e| {
+            UpstreamError::decode(Upstream::Wargaming, "clans/accountinfo", e).in_region(*region)
+        }),
+        None => Ok(None),
+    }
+}
+
+/// What a search across every region turned up.
+pub struct ServerSearch {
+    // in NA, EU, ASIA order
+    pub found: Vec<(Region, Player)>,
+    // regions that errored, the player could still be there
+    pub failed: Vec<Region>,
+}
+
+/// Everyone called `user` in any region, and the regions that couldn't be
+/// checked.
+pub async fn find_user_server(
+    client: &WargamingClient,
+    user: &str,
+) -> Result<ServerSearch, UpstreamError> {
+    let (na, eu, asia) = join!(
+        fetch_user_id(client, user, Region::NA),
+        fetch_user_id(client, user, Region::EU),
+        fetch_user_id(client, user, Region::ASIA)
+    );
+
+    // only report "not found" if every region actually answered, otherwise
+    // a rate limited region would look the same as a missing player
+    let mut found = Vec::new();
+    let mut failed = Vec::new();
+    let mut first_error = None;
+    for (region, response) in [(Region::NA, na), (Region::EU, eu), (Region::ASIA, asia)] {
+        match response {
+            Ok(Some(player)) => found.push((region, player)),
+            Ok(None) => {}
+            Err(e) => {
+                failed.push(region);
+                first_error.get_or_insert(e);
+            }
+        }
+    }
+
+    match first_error {
+        Some(e) if found.is_empty() => Err(e),
+        Some(e) => {
+            warn!("Region skipped while looking for {}: {}", user, e);
+            Ok(ServerSearch { found, failed })
+        }
+        None => Ok(ServerSearch { found, failed }),
+    }
+}
+
+/// One of several players sharing a nickname across regions, with enough
+/// to tell them apart.
+#[derive(Clone)]
+pub struct Candidate {
+    pub region: Region,
+    pub player: Player,
+    pub battles: Option<u32>,
+    pub last_battle_time: Option<SystemTime>,
+}
+
+#[derive(Deserialize)]
+struct AccountInfo {
+    #[serde(default)]
+    last_battle_time: Option<u64>,
+    #[serde(default)]
+    statistics: Option<AccountStatistics>,
+}
+
+#[derive(Deserialize)]
+struct AccountStatistics {
+    all: AccountBattles,
+}
+
+#[derive(Deserialize)]
+struct AccountBattles {
+    battles: u32,
+}
+
+/// Battle count and last battle time from account/info. A failed lookup
+/// leaves them as None, the candidate is still worth listing.
+#[tracing::instrument(skip(client, player), fields(account_id = player.account_id))]
+pub async fn fetch_candidate(
+    client: &WargamingClient,
+    region: Region,
+    player: Player,
+) -> Candidate {
+    let response = client
+        .get_account_batched(
+            "account/info",
+            client.account_application_id(),
+            &region,
+            player.account_id,
+        )
+        .await
+        .and_then(|output| {
+            output
+                .map(serde_json::from_value::<AccountInfo>)
+                .transpose()
+                .map_err(|e| {
+                    UpstreamError::decode(Upstream::Wargaming, "account/info", e).in_region(region)
+                })
+        });
+
+    let info = match response {
+        Ok(info) => info,
+        Err(e) => {
+            warn!("{}", e);
+            None
+        }
+    };
+    Candidate {
+        region,
+        player,
+        battles: info
+            .as_ref()
+            .and_then(|info| info.statistics.as_ref())
+            .map(|statistics| statistics.all.battles),
+        last_battle_time: info
+            .and_then(|info| info.last_battle_time)
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+    }
+}
+
+pub async fn generate_period_embed(
+    player_data: &PlayerData,
+    period: Period,
+) -> Result<CreateEmbed, CreatePeriodEmbedError> {
+    const DISPLAY_AMMOUNT: usize = 5;
+    let mut embed = CreateEmbed::default();
+    let data = match player_data.get_period_data(period) {
+        Some(player_data) => player_data,
+        None => {
+            return Err(CreatePeriodEmbedError::MissingRecentsError);
+        }
+    };
+
+    let mut tanks = data.tank_stats.clone();
+
+    embed
+        .title(format!("{}'s Stats", player_data.player.nickname))
+        .description(format!("**Last {} Stats**", period.nice_name()))
+        .field(
+            "Totals",
+            format!(
+                "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
+                data.overall.battles, data.overall.wn8, data.overall.winrate, data.overall.tier
+            ),
+            true,
+        )
+        .color(get_wn8_color(data.overall.wn8));
+
+    tanks.sort_by_key(|tank| tank.battles);
+    tanks.reverse();
+    tanks.truncate(DISPLAY_AMMOUNT);
+
+    for tank in tanks {
+        embed.field(
+            tank.name.to_string(),
+            format!(
+                "Battles: `{}`\nWin Rate: `{}%`\nWN8: `{}`\nDPG: `{}`",
+                tank.battles, tank.win_rate, tank.wn8, tank.dpg
+            ),
+            true,
+        );
+    }
+    Ok(embed)
+}
+
+pub async fn generate_main_stat_embed(
+    data: &PlayerData,
+) -> Result<CreateEmbed, CreateMainStatEmbedError> {
+    let overall = match data.overall.found() {
+        Some(data) => data,
+        None => return Err(CreateMainStatEmbedError::MissingOverallError),
+    };
+
+    let recents = match data.recents.found() {
+        Some(data) => data,
+        None => return Err(CreateMainStatEmbedError::MissingRecentsError),
+    };
+
+    let mut embed = CreateEmbed::default();
+
+    embed.title(format!("{}'s Stats", data.player.nickname));
+    embed.url(format!(
+        "https://tomato.gg/stats/{}/{}={}",
+        data.region.name(),
+        data.player.nickname,
+        data.player.account_id
+    ));
+
+    embed.field(
+        "**Overall**",
+        format!(
+            "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
+            overall.battles, overall.wn8, overall.win_rate, overall.tier
+        ),
+        true,
+    );
+    embed.field(
+        "**24 Hours**",
+        format!(
+            "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
+            recents.recent24hr.overall.battles,
+            recents.recent24hr.overall.wn8,
+            recents.recent24hr.overall.winrate,
+            recents.recent24hr.overall.tier
+        ),
+        true,
+    );
+
+    embed.field(
+        "**7 Days**",
+        format!(
+            "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
+            recents.recent7days.overall.battles,
+            recents.recent7days.overall.wn8,
+            recents.recent7days.overall.winrate,
+            recents.recent7days.overall.tier
+        ),
+        true,
+    );
+
+    embed.field(
+        "**30 Days**",
+        format!(
+            "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
+            recents.recent30days.overall.battles,
+            recents.recent30days.overall.wn8,
+            recents.recent30days.overall.winrate,
+            recents.recent30days.overall.tier
+        ),
+        true,
+    );
+
+    embed.field(
+        "**60 Days**",
+        format!(
+            "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
+            recents.recent60days.overall.battles,
+            recents.recent60days.overall.wn8,
+            recents.recent60days.overall.winrate,
+            recents.recent60days.overall.tier
+        ),
+        true,
+    );
+
+    embed.field(
+        "**1000 Battles**",
+        format!(
+            "Battles: `{}`\nWN8: `{}`\nWinRate: `{}%`\nAvgTier: `{}`",
+            recents.recent1000battles.overall.battles,
+            recents.recent1000battles.overall.wn8,
+            recents.recent1000battles.overall.winrate,
+            recents.recent1000battles.overall.tier
+        ),
+        true,
+    );
+    embed.footer(|f| {
+        f.text("Powered by Tomato.gg");
+        f.icon_url("https://tomato.gg/_next/image?url=%2Ftomato.png&w=48&q=75");
+        f
+    });
+    embed.color(get_wn8_color(overall.wn8));
+    if let Some(clan_info) = &data.player_clan {
+        embed.thumbnail(&clan_info.clan.emblems.x64.portal);
+        embed.description(format!(
+            "**{} at [{}]**",
+            get_short_position(&clan_info.role),
+            &clan_info.clan.tag
+        ));
+    }
+    Ok(embed)
+}
@@ -1,5 +1,6 @@
-use crate::commands::stats::Player;
-use crate::errors::FetchRecentsDataError;
+use crate::client::TomatoClient;
+use crate::player_stats::player::Player;
+use crate::player_stats::{fetch_tomato_stats, TomatoStats};
 use crate::Region;
 use serde::Deserialize;
 use serde::Deserializer;
@@ -13,11 +14,6 @@ where
     Ok(opt.unwrap_or_default())
 }
 
-#[derive(Debug, Deserialize)]
-struct RecentsResponse {
-    data: RecentsData,
-}
-
 #[derive(Debug, Deserialize, Clone, Default)]
 pub struct RecentsData {
     pub recent24hr: TimeFrame,
@@ -63,28 +59,23 @@ pub struct TankStats {
     pub win_rate: f32,
 }
 
+#[tracing::instrument(skip(client, user), fields(account_id = user.account_id))]
 pub async fn fetch_recent_data(
+    client: &TomatoClient,
     region: &Region,
     user: &Player,
     cached: bool,
-) -> Result<Option<RecentsData>, FetchRecentsDataError> {
+) -> TomatoStats<RecentsData> {
     let url = format!(
-        "{}{}",
-        format!(
-            "https://api.tomato.gg/dev/api-v2/recents/{}/{}",
-            region.extension(),
-            user.account_id
-        ),
+        "{}/recents/{}/{}{}",
+        client.api_base(),
+        region.extension(),
+        user.account_id,
         match cached {
             true => "?cache=true",
             false => "",
         }
     );
 
-    let parsed_data = reqwest::get(url).await?.json::<RecentsResponse>().await;
-
-    match parsed_data {
-        Ok(data) => Ok(Some(data.data)),
-        Err(_) => Ok(None),
-    }
+    fetch_tomato_stats(client, &url, region).await
 }
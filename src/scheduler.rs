@@ -0,0 +1,221 @@
+use crate::client::TomatoClient;
+use crate::config::SchedulerConfig;
+use crate::service::TankCaches;
+use crate::store::{Dataset, Store};
+use crate::tanks::{fetch_recent_tank_stats, fetch_tank_data, fetch_tank_economics};
+use crate::{Error, Region};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use strum::IntoEnumIterator;
+use tokio::sync::Mutex;
+use tracing::{error, warn};
+
+/// Refresh state of one dataset, per region for the regional ones.
+#[derive(Debug, Clone, Default)]
+pub struct RefreshStatus {
+    pub last_success: Option<SystemTime>,
+    pub last_error: Option<String>,
+    pub last_error_at: Option<SystemTime>,
+    // attempts since the last success, 0 when the last run went through
+    pub attempts: u32,
+    pub next_run: Option<SystemTime>,
+}
+
+// region is None for datasets that aren't split by region (economics)
+pub type StatusKey = (Dataset, Option<Region>);
+
+#[derive(Clone, Default)]
+pub struct SchedulerStatus {
+    statuses: Arc<RwLock<HashMap<StatusKey, RefreshStatus>>>,
+}
+
+impl SchedulerStatus {
+    pub fn get(&self, dataset: Dataset, region: Option<Region>) -> RefreshStatus {
+        self.statuses
+            .read()
+            .unwrap()
+            .get(&(dataset, region))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn all(&self) -> Vec<(StatusKey, RefreshStatus)> {
+        self.statuses
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(key, status)| (*key, status.clone()))
+            .collect()
+    }
+
+    /// Records when a dataset loaded from the store was saved, so its age
+    /// is known before the first refresh finishes.
+    pub fn seed(&self, dataset: Dataset, region: Option<Region>, updated_at: SystemTime) {
+        self.update((dataset, region), |s| {
+            s.last_success.get_or_insert(updated_at);
+        });
+    }
+
+    fn update(&self, key: StatusKey, f: impl FnOnce(&mut RefreshStatus)) {
+        f(self.statuses.write().unwrap().entry(key).or_default());
+    }
+}
+
+/// Keeps the cached datasets fresh. Every dataset/region pair refreshes on
+/// its own, so one region failing leaves the others (and its own stale
+/// copy) alone and just gets retried with backoff.
+pub struct Scheduler {
+    client: TomatoClient,
+    store: Store,
+    config: SchedulerConfig,
+    status: SchedulerStatus,
+}
+
+impl Scheduler {
+    pub fn new(client: TomatoClient, store: Store, config: SchedulerConfig) -> Scheduler {
+        Scheduler {
+            client,
+            store,
+            config,
+            status: SchedulerStatus::default(),
+        }
+    }
+
+    pub fn status(&self) -> SchedulerStatus {
+        self.status.clone()
+    }
+
+    pub fn start(&self, caches: &TankCaches) {
+        for region in Region::iter() {
+            let (client, store, data) = (
+                self.client.clone(),
+                self.store.clone(),
+                Arc::clone(&caches.tank_data),
+            );
+            self.spawn(
+                (Dataset::TankData, Some(region)),
+                Duration::from_secs(self.config.tank_data_interval_secs),
+                move || {
+                    let (client, store, data) = (client.clone(), store.clone(), Arc::clone(&data));
+                    async move {
+                        let tanks = fetch_tank_data(&client, &region).await?;
+                        update_region(&store, Dataset::TankData, &data, region, tanks).await;
+                        Ok(())
+                    }
+                },
+            );
+
+            let (client, store, data) = (
+                self.client.clone(),
+                self.store.clone(),
+                Arc::clone(&caches.recent_tank_stats),
+            );
+            self.spawn(
+                (Dataset::RecentTankStats, Some(region)),
+                Duration::from_secs(self.config.recent_tank_stats_interval_secs),
+                move || {
+                    let (client, store, data) = (client.clone(), store.clone(), Arc::clone(&data));
+                    async move {
+                        let stats = fetch_recent_tank_stats(&client, &region).await?;
+                        update_region(&store, Dataset::RecentTankStats, &data, region, stats).await;
+                        Ok(())
+                    }
+                },
+            );
+        }
+
+        let (client, store, tank_economics) = (
+            self.client.clone(),
+            self.store.clone(),
+            Arc::clone(&caches.tank_economics),
+        );
+        self.spawn(
+            (Dataset::TankEconomics, None),
+            Duration::from_secs(self.config.tank_economics_interval_secs),
+            move || {
+                let (client, store, data) =
+                    (client.clone(), store.clone(), Arc::clone(&tank_economics));
+                async move {
+                    let economics = fetch_tank_economics(&client).await?;
+                    save_dataset(&store, Dataset::TankEconomics, None, &economics).await;
+                    *data.lock().await = economics;
+                    Ok(())
+                }
+            },
+        );
+    }
+
+    fn spawn<F, Fut>(&self, key: StatusKey, interval: Duration, mut refresh: F)
+    where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send,
+    {
+        let status = self.status.clone();
+        let retry_base_delay = Duration::from_secs(self.config.retry_base_delay_secs);
+        let retry_max_delay = Duration::from_secs(self.config.retry_max_delay_secs);
+
+        tokio::spawn(async move {
+            loop {
+                status.update(key, |s| s.attempts += 1);
+
+                let delay = match refresh().await {
+                    Ok(()) => {
+                        status.update(key, |s| {
+                            s.last_success = Some(SystemTime::now());
+                            s.attempts = 0;
+                            s.next_run = Some(SystemTime::now() + interval);
+                        });
+                        interval
+                    }
+                    Err(e) => {
+                        warn!(dataset = ?key.0, region = ?key.1, error = %e, "Refresh failed");
+                        let attempts = status.get(key.0, key.1).attempts;
+                        let delay = retry_delay(retry_base_delay, retry_max_delay, attempts);
+                        status.update(key, |s| {
+                            s.last_error = Some(e.to_string());
+                            s.last_error_at = Some(SystemTime::now());
+                            s.next_run = Some(SystemTime::now() + delay);
+                        });
+                        delay
+                    }
+                };
+
+                tokio::time::sleep(delay).await;
+            }
+        });
+    }
+}
+
+/// How long to wait before retrying a dataset that failed `attempts` times
+/// in a row, doubling from `base` up to `max`.
+pub fn retry_delay(base: Duration, max: Duration, attempts: u32) -> Duration {
+    let exponent = attempts.saturating_sub(1).min(16);
+    base.saturating_mul(1 << exponent).min(max)
+}
+
+async fn update_region<T>(
+    store: &Store,
+    dataset: Dataset,
+    data: &Mutex<HashMap<Region, Vec<T>>>,
+    region: Region,
+    value: Vec<T>,
+) where
+    T: Serialize,
+{
+    save_dataset(store, dataset, Some(region), &value).await;
+    data.lock().await.insert(region, value);
+}
+
+async fn save_dataset<T: Serialize>(
+    store: &Store,
+    dataset: Dataset,
+    region: Option<Region>,
+    value: &T,
+) {
+    if let Err(e) = store.save(dataset, region, value).await {
+        error!(?dataset, ?region, error = %e, "Error saving dataset to store");
+    }
+}
@@ -0,0 +1,77 @@
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::get;
+use axum::Router;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tomatobot::metrics::Metrics;
+use tomatobot::scheduler::SchedulerStatus;
+use tomatobot::service::TankCaches;
+use tomatobot::Error;
+use tracing::info;
+
+#[derive(Clone)]
+pub struct ServerState {
+    pub metrics: Metrics,
+    pub refresh_status: SchedulerStatus,
+    pub gateway_connected: Arc<AtomicBool>,
+    pub caches: TankCaches,
+}
+
+/// Small HTTP server next to the bot for ops endpoints, only started when
+/// enabled in the config.
+pub async fn serve(bind: SocketAddr, state: ServerState) -> Result<(), Error> {
+    let app = Router::new()
+        .route("/metrics", get(metrics))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .with_state(state);
+
+    info!(%bind, "Serving HTTP endpoints");
+    axum::Server::try_bind(&bind)?
+        .serve(app.into_make_service())
+        .await?;
+    Ok(())
+}
+
+async fn metrics(State(state): State<ServerState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(&state.refresh_status),
+    )
+}
+
+// alive as long as we can answer, healthy once the gateway is connected
+async fn healthz(State(state): State<ServerState>) -> impl IntoResponse {
+    if state.gateway_connected.load(Ordering::Relaxed) {
+        (StatusCode::OK, String::from("ok"))
+    } else {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            String::from("gateway not connected"),
+        )
+    }
+}
+
+/// Ready once every region has tank data and recent stats, and economics
+/// are loaded.
+async fn readyz(State(state): State<ServerState>) -> impl IntoResponse {
+    let missing = state.caches.missing().await;
+    if missing.is_empty() {
+        return (StatusCode::OK, String::from("ready"));
+    }
+
+    let missing: Vec<String> = missing
+        .into_iter()
+        .map(|(dataset, region)| match region {
+            Some(region) => format!("{} {:?}", dataset.key(), region),
+            None => dataset.key().to_string(),
+        })
+        .collect();
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        format!("missing: {}", missing.join(", ")),
+    )
+}
@@ -0,0 +1,462 @@
+use crate::clans::{fetch_all_clan, fetch_clan_id, ClanData};
+use crate::client::{TomatoClient, WargamingClient};
+use crate::errors::UpstreamError;
+use crate::player_stats::overall::fetch_overall_data;
+use crate::player_stats::player::{
+    fetch_candidate, fetch_clan_info, fetch_user_id, find_user_server, search_players,
+    valid_nickname, Candidate, Player, PlayerData,
+};
+use crate::player_stats::recents::fetch_recent_data;
+use crate::player_stats::TomatoStats;
+use crate::scheduler::SchedulerStatus;
+use crate::store::{Dataset, Store};
+use crate::tanks::{fuzzy_find_tank, DatasetAges, RecentTankStats, Tank, TankEconomics};
+use crate::vehicle::{Nation, TankClass};
+use crate::Region;
+use futures::future::join_all;
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use strum::IntoEnumIterator;
+use tokio::join;
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+
+/// The datasets /marks works from, kept fresh by the scheduler.
+#[derive(Clone, Default)]
+pub struct TankCaches {
+    pub tank_data: Arc<Mutex<HashMap<Region, Vec<Tank>>>>,
+    pub tank_economics: Arc<Mutex<Vec<TankEconomics>>>,
+    pub recent_tank_stats: Arc<Mutex<HashMap<Region, Vec<RecentTankStats>>>>,
+}
+
+impl TankCaches {
+    /// Whatever was saved last run, so lookups work right away while the
+    /// scheduler replaces it in the background.
+    pub async fn load(store: &Store, refresh_status: &SchedulerStatus) -> TankCaches {
+        TankCaches {
+            tank_data: Arc::new(Mutex::new(
+                load_regions(store, refresh_status, Dataset::TankData).await,
+            )),
+            tank_economics: Arc::new(Mutex::new(
+                load_dataset(store, refresh_status, Dataset::TankEconomics, None)
+                    .await
+                    .unwrap_or_default(),
+            )),
+            recent_tank_stats: Arc::new(Mutex::new(
+                load_regions(store, refresh_status, Dataset::RecentTankStats).await,
+            )),
+        }
+    }
+
+    /// The datasets still missing, by region where they're per region. Ready
+    /// once this is empty, ie /marks can answer for any region.
+    pub async fn missing(&self) -> Vec<(Dataset, Option<Region>)> {
+        let mut missing = Vec::new();
+
+        let tank_data = self.tank_data.lock().await;
+        let recent_tank_stats = self.recent_tank_stats.lock().await;
+        for region in Region::iter() {
+            if !loaded(&tank_data, region) {
+                missing.push((Dataset::TankData, Some(region)));
+            }
+            if !loaded(&recent_tank_stats, region) {
+                missing.push((Dataset::RecentTankStats, Some(region)));
+            }
+        }
+        if self.tank_economics.lock().await.is_empty() {
+            missing.push((Dataset::TankEconomics, None));
+        }
+        missing
+    }
+}
+
+// an empty list counts as missing, /marks can't answer from one either
+fn loaded<T>(map: &HashMap<Region, Vec<T>>, region: Region) -> bool {
+    map.get(&region).is_some_and(|v| !v.is_empty())
+}
+
+// each region was saved on its own, so each gets its own age
+async fn load_regions<T>(
+    store: &Store,
+    refresh_status: &SchedulerStatus,
+    dataset: Dataset,
+) -> HashMap<Region, Vec<T>>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let mut map = HashMap::new();
+    for region in Region::iter() {
+        if let Some(value) = load_dataset(store, refresh_status, dataset, Some(region)).await {
+            map.insert(region, value);
+        }
+    }
+    map
+}
+
+async fn load_dataset<T>(
+    store: &Store,
+    refresh_status: &SchedulerStatus,
+    dataset: Dataset,
+    region: Option<Region>,
+) -> Option<T>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    match store.load(dataset, region).await {
+        Ok(Some(stored)) => {
+            let age = stored.updated_at.elapsed().unwrap_or_default();
+            info!(?dataset, ?region, ?age, "Loaded dataset from store");
+            refresh_status.seed(dataset, region, stored.updated_at);
+            Some(stored.value)
+        }
+        Ok(None) => None,
+        Err(e) => {
+            error!(?dataset, ?region, error = %e, "Error loading dataset from store");
+            None
+        }
+    }
+}
+
+fn refresh_stats<T>(current: &mut TomatoStats<T>, fresh: TomatoStats<T>) {
+    if fresh.found().is_some() || current.found().is_none() {
+        *current = fresh;
+    }
+}
+
+async fn fresh_or_cached<T, F, Fut>(fetch: F) -> TomatoStats<T>
+where
+    F: Fn(bool) -> Fut,
+    Fut: Future<Output = TomatoStats<T>>,
+{
+    let fresh = fetch(false).await;
+    if fresh.found().is_some() {
+        return fresh;
+    }
+    let mut cached = fetch(true).await;
+    // the cached copy only wins when it has stats, the fresh reason is the
+    // more current one otherwise
+    refresh_stats(&mut cached, fresh);
+    cached
+}
+
+pub enum PlayerLookup {
+    NotFound,
+    Found(Region, Player),
+    // the name exists in several regions, or in one while another couldn't
+    // be checked, the user has to pick
+    Ambiguous {
+        candidates: Vec<Candidate>,
+        unchecked: Vec<Region>,
+    },
+}
+
+impl PlayerLookup {
+    pub fn found(self) -> Option<(Region, Player)> {
+        match self {
+            PlayerLookup::Found(region, player) => Some((region, player)),
+            _ => None,
+        }
+    }
+}
+
+pub enum TankLookup {
+    // the region's tank data hasn't loaded yet
+    NotLoaded { next_run: Option<SystemTime> },
+    // no tank passes the filter
+    NoMatch,
+    Found(Box<TankMarks>),
+}
+
+/// Narrows /marks down to one nation and/or class before fuzzy matching.
+#[derive(Debug, Clone, Default)]
+pub struct TankFilter {
+    pub nation: Option<Nation>,
+    pub class: Option<TankClass>,
+}
+
+impl TankFilter {
+    pub fn matches(&self, tank: &Tank) -> bool {
+        self.nation
+            .as_ref()
+            .is_none_or(|nation| tank.nation == *nation)
+            && self.class.as_ref().is_none_or(|class| tank.class == *class)
+    }
+}
+
+pub struct TankMarks {
+    pub tank: Tank,
+    pub economics: Option<TankEconomics>,
+    pub recent_stats: Option<RecentTankStats>,
+    pub ages: DatasetAges,
+}
+
+// autocomplete asks again on every keystroke, typing back and forth over
+// the same prefix shouldn't cost a request each time
+const SEARCH_CACHE_TTL: Duration = Duration::from_secs(60);
+/// Discord shows at most 25 autocomplete choices, so no search asks for
+/// more.
+pub const AUTOCOMPLETE_LIMIT: usize = 25;
+
+type SearchCache = Arc<Mutex<HashMap<(Region, String), (Instant, Vec<Player>)>>>;
+
+/// Player, clan and tank lookups on top of the upstream clients and the
+/// cached datasets. Cheap to clone.
+#[derive(Clone)]
+pub struct StatsService {
+    tomato: TomatoClient,
+    wargaming: WargamingClient,
+    caches: TankCaches,
+    refresh_status: SchedulerStatus,
+    searches: SearchCache,
+}
+
+impl StatsService {
+    pub fn new(
+        tomato: TomatoClient,
+        wargaming: WargamingClient,
+        caches: TankCaches,
+        refresh_status: SchedulerStatus,
+    ) -> StatsService {
+        StatsService {
+            tomato,
+            wargaming,
+            caches,
+            refresh_status,
+            searches: SearchCache::default(),
+        }
+    }
+
+    pub fn tomato(&self) -> &TomatoClient {
+        &self.tomato
+    }
+
+    pub fn wargaming(&self) -> &WargamingClient {
+        &self.wargaming
+    }
+
+    pub fn caches(&self) -> &TankCaches {
+        &self.caches
+    }
+
+    pub fn refresh_status(&self) -> &SchedulerStatus {
+        &self.refresh_status
+    }
+
+    /// Looks the exact name up in `region`, or in every region when there's
+    /// none.
+    pub async fn find_player(
+        &self,
+        user: &str,
+        region: Option<Region>,
+    ) -> Result<PlayerLookup, UpstreamError> {
+        let (mut found, unchecked) = match region {
+            Some(region) => match fetch_user_id(&self.wargaming, user, region).await? {
+                Some(player) => (vec![(region, player)], Vec::new()),
+                None => (Vec::new(), Vec::new()),
+            },
+            None => {
+                let search = find_user_server(&self.wargaming, user).await?;
+                (search.found, search.failed)
+            }
+        };
+
+        // a single match only counts when every region answered, the one
+        // that didn't could have the player that was meant
+        if found.len() <= 1 && unchecked.is_empty() {
+            return Ok(match found.pop() {
+                Some((region, player)) => PlayerLookup::Found(region, player),
+                None => PlayerLookup::NotFound,
+            });
+        }
+
+        let candidates = join_all(
+            found
+                .into_iter()
+                .map(|(region, player)| fetch_candidate(&self.wargaming, region, player)),
+        )
+        .await;
+        Ok(PlayerLookup::Ambiguous {
+            candidates,
+            unchecked,
+        })
+    }
+
+    /// Players in `region` whose nickname starts with `prefix`, for
+    /// autocomplete. Answers are reused for a minute.
+    pub async fn search_players(
+        &self,
+        region: Region,
+        prefix: &str,
+    ) -> Result<Vec<Player>, UpstreamError> {
+        // WG wants at least 3 characters
+        if prefix.len() < 3 || !valid_nickname(prefix) {
+            return Ok(Vec::new());
+        }
+
+        let key = (region, prefix.to_ascii_lowercase());
+        {
+            let mut searches = self.searches.lock().await;
+            searches.retain(|_, (searched_at, _)| searched_at.elapsed() < SEARCH_CACHE_TTL);
+            if let Some((_, players)) = searches.get(&key) {
+                return Ok(players.clone());
+            }
+        }
+
+        let players = search_players(&self.wargaming, prefix, region, AUTOCOMPLETE_LIMIT).await?;
+        self.searches
+            .lock()
+            .await
+            .insert(key, (Instant::now(), players.clone()));
+        Ok(players)
+    }
+
+    /// Quick first pass from Tomato's cache plus the player's clan, enough
+    /// for a first embed. A failed clan lookup is logged and left as None.
+    pub async fn player_data(&self, region: Region, player: Player) -> PlayerData {
+        let (overall, recents, player_clan) = join!(
+            fetch_overall_data(&self.tomato, &region, &player, true),
+            fetch_recent_data(&self.tomato, &region, &player, true),
+            fetch_clan_info(&self.wargaming, &region, &player.account_id)
+        );
+
+        let player_clan = match player_clan {
+            Ok(player_clan) => player_clan,
+            Err(e) => {
+                warn!("{}", e);
+                None
+            }
+        };
+        PlayerData {
+            player_clan,
+            clan: None,
+            player,
+            region,
+            overall,
+            recents,
+            is_in_clan: false,
+        }
+    }
+
+    /// Second pass with uncached stats, and the clan's stats when the player
+    /// has one. Keeps the cached stats when the refresh doesn't find any. A
+    /// clan whose stats fail to load counts as no clan.
+    pub async fn refresh_player_data(&self, data: &mut PlayerData) {
+        let clan_id = data.player_clan.as_ref().map(|c| c.clan.clan_id);
+
+        let (clan, overall, recents) = join!(
+            async {
+                match clan_id {
+                    Some(clan_id) => Some(self.clan_data(data.region, clan_id).await),
+                    None => None,
+                }
+            },
+            fetch_overall_data(&self.tomato, &data.region, &data.player, false),
+            fetch_recent_data(&self.tomato, &data.region, &data.player, false),
+        );
+
+        match clan {
+            Some(Ok(clan)) => data.clan = Some(clan),
+            Some(Err(e)) => warn!("{}", e),
+            None => {}
+        }
+        data.is_in_clan = data.clan.is_some();
+        refresh_stats(&mut data.overall, overall);
+        refresh_stats(&mut data.recents, recents);
+    }
+
+    /// Only the uncached stats, no clan and no quick first pass, for when
+    /// nothing gets shown until they're in. Tomato's cache is asked only for
+    /// what the uncached fetch didn't find.
+    pub async fn player_stats(&self, region: Region, player: Player) -> PlayerData {
+        let (overall, recents) = join!(
+            fresh_or_cached(|cached| fetch_overall_data(&self.tomato, &region, &player, cached)),
+            fresh_or_cached(|cached| fetch_recent_data(&self.tomato, &region, &player, cached)),
+        );
+
+        PlayerData {
+            player_clan: None,
+            clan: None,
+            player,
+            region,
+            overall,
+            recents,
+            is_in_clan: false,
+        }
+    }
+
+    pub async fn find_clan(&self, region: Region, tag: &str) -> Result<Option<u32>, UpstreamError> {
+        fetch_clan_id(&self.wargaming, region, tag).await
+    }
+
+    pub async fn clan_data(&self, region: Region, clan_id: u32) -> Result<ClanData, UpstreamError> {
+        fetch_all_clan(&self.tomato, &self.wargaming, region, clan_id).await
+    }
+
+    /// Fuzzy matches `input` against the region's cached tanks that pass
+    /// `filter`.
+    pub async fn find_tank(&self, region: Region, input: &str, filter: &TankFilter) -> TankLookup {
+        let tank_map = self.caches.tank_data.lock().await;
+        let tanks = match tank_map.get(&region) {
+            Some(tanks) if !tanks.is_empty() => tanks,
+            _ => {
+                let status = self.refresh_status.get(Dataset::TankData, Some(region));
+                return TankLookup::NotLoaded {
+                    next_run: status.next_run,
+                };
+            }
+        };
+        let tanks: Vec<Tank> = tanks
+            .iter()
+            .filter(|tank| filter.matches(tank))
+            .cloned()
+            .collect();
+        if tanks.is_empty() {
+            return TankLookup::NoMatch;
+        }
+        let tank_name = fuzzy_find_tank(input, &tanks);
+        let tank = tanks
+            .into_iter()
+            .find(|tank| tank.name == tank_name)
+            .expect("fuzzy match picks from the same list");
+        drop(tank_map);
+
+        let economics = self
+            .caches
+            .tank_economics
+            .lock()
+            .await
+            .iter()
+            .find(|t| t.id == tank.id)
+            .cloned();
+        let recent_stats = self
+            .caches
+            .recent_tank_stats
+            .lock()
+            .await
+            .get(&region)
+            .and_then(|stats| stats.iter().find(|t| t.tank_id == tank.id).cloned());
+
+        let now = SystemTime::now();
+        let age = |dataset, region| {
+            self.refresh_status
+                .get(dataset, region)
+                .last_success
+                .and_then(|at| now.duration_since(at).ok())
+        };
+        let ages = DatasetAges {
+            tank_data: age(Dataset::TankData, Some(region)),
+            economics: age(Dataset::TankEconomics, None),
+            server_stats: age(Dataset::RecentTankStats, Some(region)),
+        };
+
+        TankLookup::Found(Box::new(TankMarks {
+            tank,
+            economics,
+            recent_stats,
+            ages,
+        }))
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+use tokio::sync::watch;
+
+/// Shared shutdown state. Commands check it before starting, and messages
+/// that still need a final edit register so shutdown can wait for them to
+/// strip their components before the shards go down.
+#[derive(Clone)]
+pub struct Shutdown {
+    inner: Arc<ShutdownInner>,
+}
+
+struct ShutdownInner {
+    requested: watch::Sender<bool>,
+    in_flight: watch::Sender<usize>,
+}
+
+impl Default for Shutdown {
+    fn default() -> Self {
+        Shutdown::new()
+    }
+}
+
+impl Shutdown {
+    pub fn new() -> Shutdown {
+        Shutdown {
+            inner: Arc::new(ShutdownInner {
+                requested: watch::channel(false).0,
+                in_flight: watch::channel(0).0,
+            }),
+        }
+    }
+
+    pub fn request(&self) {
+        self.inner.requested.send_replace(true);
+    }
+
+    pub fn is_requested(&self) -> bool {
+        *self.inner.requested.borrow()
+    }
+
+    /// Resolves once shutdown has been requested.
+    pub async fn requested(&self) {
+        let mut rx = self.inner.requested.subscribe();
+        // the sender lives in self, so this can't error
+        let _ = rx.wait_for(|requested| *requested).await;
+    }
+
+    /// Marks a message as needing a final edit until the guard is dropped.
+    pub fn track(&self) -> InFlightGuard {
+        self.inner.in_flight.send_modify(|n| *n += 1);
+        InFlightGuard {
+            shutdown: self.clone(),
+        }
+    }
+
+    /// Resolves once every tracked message has been cleaned up.
+    pub async fn drained(&self) {
+        let mut rx = self.inner.in_flight.subscribe();
+        let _ = rx.wait_for(|n| *n == 0).await;
+    }
+}
+
+pub struct InFlightGuard {
+    shutdown: Shutdown,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.shutdown.inner.in_flight.send_modify(|n| *n -= 1);
+    }
+}
+
+/// Waits for SIGTERM, or ctrl-c when running by hand.
+pub async fn signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("failed to listen for SIGTERM");
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
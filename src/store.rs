@@ -0,0 +1,208 @@
+use crate::errors::StoreError;
+use crate::Region;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Dataset {
+    TankData,
+    TankEconomics,
+    RecentTankStats,
+}
+
+impl Dataset {
+    pub fn key(&self) -> &'static str {
+        match self {
+            Dataset::TankData => "tank_data",
+            Dataset::TankEconomics => "tank_economics",
+            Dataset::RecentTankStats => "recent_tank_stats",
+        }
+    }
+}
+
+pub struct Stored<T> {
+    pub value: T,
+    pub updated_at: SystemTime,
+}
+
+/// The WoT account a Discord user picked with /link.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkedAccount {
+    pub region: Region,
+    pub account_id: u32,
+    // as it was when linked, only used for display
+    pub nickname: String,
+}
+
+/// SQLite file holding the last good copy of each cached dataset so a
+/// restart doesn't have to wait on Tomato before /marks works, plus the
+/// accounts users linked.
+#[derive(Clone)]
+pub struct Store {
+    conn: Arc<Mutex<Connection>>,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Store, StoreError> {
+        let conn = Connection::open(path)?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS datasets (
+                name TEXT NOT NULL,
+                region TEXT NOT NULL,
+                updated_at INTEGER NOT NULL,
+                payload TEXT NOT NULL,
+                PRIMARY KEY (name, region)
+            );
+            CREATE TABLE IF NOT EXISTS linked_accounts (
+                discord_id INTEGER PRIMARY KEY,
+                region TEXT NOT NULL,
+                account_id INTEGER NOT NULL,
+                nickname TEXT NOT NULL,
+                linked_at INTEGER NOT NULL
+            );",
+        )?;
+
+        Ok(Store {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Saves one region's copy of `dataset`, or the only copy when the
+    /// dataset isn't split by region.
+    pub async fn save<T: Serialize>(
+        &self,
+        dataset: Dataset,
+        region: Option<Region>,
+        value: &T,
+    ) -> Result<(), StoreError> {
+        let payload = serde_json::to_string(value)?;
+        let updated_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        self.blocking(move |conn| {
+            conn.execute(
+                "INSERT INTO datasets (name, region, updated_at, payload)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(name, region) DO UPDATE SET updated_at = ?3, payload = ?4",
+                params![dataset.key(), region_key(region), updated_at, payload],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    pub async fn load<T: DeserializeOwned + Send + 'static>(
+        &self,
+        dataset: Dataset,
+        region: Option<Region>,
+    ) -> Result<Option<Stored<T>>, StoreError> {
+        let row: Option<(i64, String)> = self
+            .blocking(move |conn| {
+                Ok(conn
+                    .query_row(
+                        "SELECT updated_at, payload FROM datasets
+                         WHERE name = ?1 AND region = ?2",
+                        params![dataset.key(), region_key(region)],
+                        |row| Ok((row.get(0)?, row.get(1)?)),
+                    )
+                    .optional()?)
+            })
+            .await?;
+
+        match row {
+            Some((updated_at, payload)) => Ok(Some(Stored {
+                value: serde_json::from_str(&payload)?,
+                updated_at: UNIX_EPOCH + Duration::from_secs(updated_at as u64),
+            })),
+            None => Ok(None),
+        }
+    }
+
+    /// Links `discord_id` to `account`, replacing any earlier link.
+    pub async fn link(&self, discord_id: u64, account: &LinkedAccount) -> Result<(), StoreError> {
+        let account = account.clone();
+        let linked_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+
+        self.blocking(move |conn| {
+            conn.execute(
+                "INSERT INTO linked_accounts (discord_id, region, account_id, nickname, linked_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT(discord_id) DO UPDATE
+                 SET region = ?2, account_id = ?3, nickname = ?4, linked_at = ?5",
+                params![
+                    discord_id as i64,
+                    account.region.name(),
+                    account.account_id,
+                    account.nickname,
+                    linked_at
+                ],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Returns whether there was a link to remove.
+    pub async fn unlink(&self, discord_id: u64) -> Result<bool, StoreError> {
+        self.blocking(move |conn| {
+            let removed = conn.execute(
+                "DELETE FROM linked_accounts WHERE discord_id = ?1",
+                params![discord_id as i64],
+            )?;
+            Ok(removed > 0)
+        })
+        .await
+    }
+
+    pub async fn linked_account(
+        &self,
+        discord_id: u64,
+    ) -> Result<Option<LinkedAccount>, StoreError> {
+        let row: Option<(String, u32, String)> = self
+            .blocking(move |conn| {
+                Ok(conn
+                    .query_row(
+                        "SELECT region, account_id, nickname FROM linked_accounts
+                         WHERE discord_id = ?1",
+                        params![discord_id as i64],
+                        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                    )
+                    .optional()?)
+            })
+            .await?;
+
+        match row {
+            Some((region, account_id, nickname)) => Ok(Some(LinkedAccount {
+                region: Region::from_str(&region).map_err(|_| StoreError::UnknownRegion(region))?,
+                account_id,
+                nickname,
+            })),
+            None => Ok(None),
+        }
+    }
+
+    // sqlite calls block, keep them off the async workers
+    async fn blocking<R, F>(&self, f: F) -> Result<R, StoreError>
+    where
+        R: Send + 'static,
+        F: FnOnce(&Connection) -> Result<R, StoreError> + Send + 'static,
+    {
+        let conn = Arc::clone(&self.conn);
+        tokio::task::spawn_blocking(move || f(&conn.lock().unwrap())).await?
+    }
+}
+
+// NULL never conflicts in a primary key, so the unsplit datasets get ""
+fn region_key(region: Option<Region>) -> &'static str {
+    region.map_or("", |region| region.name())
+}
@@ -0,0 +1,434 @@
+use crate::client::TomatoClient;
+use crate::emoji::Emojis;
+use crate::errors::{Upstream, UpstreamError, UpstreamErrorKind};
+use crate::get_wn8_color;
+use crate::vehicle::{Nation, TankClass, Tier};
+use crate::Region;
+use fuzzywuzzy::fuzz;
+use fuzzywuzzy::process;
+use fuzzywuzzy::utils;
+use poise::serenity_prelude::CreateEmbed;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+#[derive(Deserialize)]
+pub struct MarkResponse {
+    meta: MetaData,
+    data: Vec<Tank>,
+}
+
+#[derive(Deserialize)]
+pub struct MetaData {
+    status: String,
+}
+
+// I FUCKING HATE JSON WHY WHY WHY
+// WHY THE FUCK DO THE INTS BECOME STRINGS WHEN THE DATA IS MISSING
+// WHO THE FUCK DESIGNED THIS SYSTEM
+// GARGLE MY FUCKING NUTS
+// DYNAMICALLY TYPED LANGUAGES WERE A FUCKING MISTAKE
+fn deserialize_str_to_u32<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value: Value = serde::Deserialize::deserialize(deserializer)?;
+
+    match value {
+        Value::Number(n) => {
+            if let Some(v) = n.as_u64() {
+                Ok(v as u32)
+            } else {
+                Err(serde::de::Error::custom("Expected u32 for third"))
+            }
+        }
+        Value::String(_) => {
+            debug!("Missing Value in Tanks");
+
+            Ok(0) // treat any string as a zero
+        }
+        _ => Err(serde::de::Error::custom("Expected u32 or String for third")),
+    }
+}
+
+
+
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RecentTankStats {
+    pub tank_id: u32,
+    name: String,
+    nation: Nation,
+    tier: Tier,
+    class: TankClass,
+    image: String,
+    big_image: String,
+    battles: u32,
+    winrate: f64,
+    player_winrate: f64,
+    winrate_differential: f64,
+    damage: u32,
+    sniper_damage: u32,
+    frags: f64,
+    shots_fired: f64,
+    direct_hits: f64,
+    penetrations: f64,
+    hit_rate: f64,
+    pen_rate: f64,
+    spotting_assist: u32,
+    tracking_assist: u32,
+    spots: f64,
+    damage_blocked: u32,
+    damage_received: u32,
+    potential_damage_received: u32,
+    base_capture_points: f64,
+    base_defense_points: f64,
+    life_time: u32,
+    survival: f64,
+    distance_traveled: u32,
+    wn8: u32,
+    #[serde(rename = "isPrem")]
+    is_prem: bool,
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn fetch_recent_tank_stats(client: &TomatoClient, region: &Region) 
+    -> Result<Vec<RecentTankStats>, UpstreamError> {
+    let start = Instant::now();
+    let recent_tank_stats_url = format!("{}/all-tanks-server-stats-wr-range/{}/0/100?cache=true",client.api_base(),region.extension());
+    let response = client
+        .get_json::<Vec<RecentTankStats>>(&recent_tank_stats_url, Some(*region))
+        .await?;
+    if response.is_empty() {
+        return Err(UpstreamError::new(
+            Upstream::Tomato,
+            "all-tanks-server-stats-wr-range",
+            UpstreamErrorKind::Empty,
+        )
+        .in_region(*region));
+    }
+    let duration = start.elapsed();
+    info!(region = region.name(), ?duration, "Fetched Recent Tank Stats");
+    Ok(response)
+}
+
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct TankEconomicsResponse {
+    data: Vec<TankEconomics>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TankEconomics {
+    #[serde(rename = "tank_id")]
+    pub id: u32,
+    pub battles: u32,
+    pub avg_earnings: u32,
+    pub avg_profit: i32,
+    pub avg_ammo_cost: u32,
+    pub cost_per_shot: u32,
+    pub earnings_per_minute: u32,
+    pub profit_per_minute: i32,
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn fetch_tank_economics(client: &TomatoClient) -> Result<Vec<TankEconomics>, UpstreamError> {
+    let start = Instant::now();
+    let economics_url = format!("{}/tank-economics", client.api_base());
+    let response = client
+        .get_json::<TankEconomicsResponse>(&economics_url, None)
+        .await?;
+    if response.data.is_empty() {
+        return Err(UpstreamError::new(
+            Upstream::Tomato,
+            "tank-economics",
+            UpstreamErrorKind::Empty,
+        ));
+    }
+
+    let duration = start.elapsed();
+    info!(?duration, "Fetched Tank Economics");
+    Ok(response.data)
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Tank {
+    pub id: u32,
+    pub nation: Nation,
+    #[serde(rename = "isPrem")]
+    pub is_prem: bool,
+    pub class: TankClass,
+    pub name: String,
+    pub tier: Tier,
+    #[serde(default)]
+    #[serde(rename = "65")]
+    pub pct_65: u32,
+    #[serde(default)]
+    #[serde(rename = "85")]
+    pub pct_85: u32,
+    #[serde(default)]
+    #[serde(rename = "95")]
+    pub pct_95: u32,
+    #[serde(default)]
+    #[serde(rename = "100")]
+    pub pct_100: u32,
+    #[serde(default)]
+    #[serde(rename = "1st")]
+    pub first: u32,
+    #[serde(default)]
+    #[serde(rename = "2nd")]
+    pub second: u32,
+    #[serde(default, deserialize_with = "deserialize_str_to_u32")]
+    #[serde(rename = "3rd")]
+    pub third: u32,
+    #[serde(default)]
+    pub ace: u32,
+    #[serde(default)]
+    pub images: Images,
+}
+impl Default for Tank {
+    fn default() -> Tank {
+        Tank {
+            id: 16897,
+            name: String::from("Obj. 140"),
+            tier: Tier(10),
+            class: TankClass::MT,
+            is_prem: false,
+            nation: Nation::USSR,
+            pct_65: 6969,
+            pct_85: 6969,
+            pct_95: 6969,
+            pct_100: 6969,
+            first: 6969,
+            second:6969,
+            third: 6969,
+            ace: 6969,
+            images: Images {
+                big_icon: String::from("https://api.worldoftanks.com/static/2.71.0/wot/encyclopedia/vehicle/ussr-R97_Object_140.png"),
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct Images {
+    big_icon: String,
+}
+
+#[tracing::instrument(skip(client))]
+pub async fn fetch_tank_data(client: &TomatoClient, region: &Region) -> Result<Vec<Tank>, UpstreamError> {
+    let start = Instant::now();
+    let moe_url = format!(
+        "{}/moe/{}",
+        client.api_base(),
+        region.extension()
+    );
+    let mastery_url = format!(
+        "{}/mastery/{}",
+        client.api_base(),
+        region.extension()
+    );
+    let moe: MarkResponse = client.get_json(&moe_url, Some(*region)).await?;
+    let mastery: MarkResponse = client.get_json(&mastery_url, Some(*region)).await?;
+    // a refresh that errors keeps the last good copy, one that "works" with
+    // missing data would replace it
+    for (endpoint, response) in [("moe", &moe), ("mastery", &mastery)] {
+        if response.meta.status != "ok" {
+            return Err(
+                UpstreamError::new(Upstream::Tomato, endpoint, UpstreamErrorKind::Unavailable)
+                    .in_region(*region),
+            );
+        }
+    }
+
+    let tanks: Vec<Tank> = moe
+        .data
+        .into_iter()
+        .filter_map(|tank1| {
+            let matching_tank2 = mastery.data.iter().find(|tank2| tank2.id == tank1.id);
+            matching_tank2.map(|tank2| Tank {
+                id: tank1.id,
+                name: tank1.name,
+                tier: tank1.tier,
+                pct_65: tank1.pct_65,
+                pct_85: tank1.pct_85,
+                nation: tank1.nation,
+                is_prem: tank1.is_prem,
+                class: tank1.class,
+                pct_95: tank1.pct_95,
+                pct_100: tank1.pct_100,
+                first: tank2.first,
+                second: tank2.second,
+                third: tank2.third,
+                ace: tank2.ace,
+                images: tank2.images.clone(),
+            })
+        })
+        .collect();
+    if tanks.is_empty() {
+        return Err(UpstreamError::new(Upstream::Tomato, "moe", UpstreamErrorKind::Empty)
+            .in_region(*region));
+    }
+    let duration = start.elapsed();
+    info!(region = region.name(), ?duration, "Fetched Tanks");
+    Ok(tanks)
+}
+
+pub fn fuzzy_find_tank(input: &str, tanks: &[Tank]) -> String {
+    let tank_name_list: Vec<String> = tanks.iter().map(|t| t.name.clone()).collect();
+    let expected_name = process::extract_one(
+        input,
+        &tank_name_list,
+        utils::full_process,
+        fuzz::wratio,
+        0,
+    )
+    .unwrap()
+    .0;
+    debug!(input, found = %expected_name, "Fuzzy matched tank");
+    expected_name
+}
+
+pub fn create_tank_embed_description(tank: &Tank, emojis: &Emojis) -> String {
+    let class_emoji = get_class_emoji(emojis, tank.is_prem, &tank.class);
+    let nation_emoji = get_nation_emoji(emojis, &tank.nation);
+    format!("{} {} Tier {}", nation_emoji, class_emoji, tank.tier)
+}
+
+// unknown classes and nations fall back to their name so nothing odd ends up
+// in the embed
+pub fn get_class_emoji<'a>(emojis: &'a Emojis, is_prem: bool, class: &'a TankClass) -> &'a str {
+    if is_prem {
+        match class {
+            TankClass::MT => {return emojis.get("premMT")},
+            TankClass::LT => {return emojis.get("premLT")},
+            TankClass::HT => {return emojis.get("premHT")},
+            TankClass::SPG => {return emojis.get("premSPG")},
+            TankClass::TD => {return emojis.get("premTD")},
+            TankClass::Unknown(_) => {return class.name()},
+        }
+    }
+
+    match class {
+        TankClass::Unknown(_) => {class.name()},
+        // the emojis are named after the class
+        known => {emojis.get(known.as_str())},
+    }
+}
+
+pub fn get_nation_emoji<'a>(emojis: &'a Emojis, nation: &'a Nation) -> &'a str{
+
+    match nation {
+        Nation::France => {emojis.get("France")},
+        Nation::USSR => {emojis.get("USSR")},
+        Nation::Germany => {emojis.get("Germany")},
+        Nation::China => {emojis.get("China")},
+        Nation::Poland => {emojis.get("Poland")},
+        Nation::UK => {emojis.get("UK")},
+        Nation::USA => {emojis.get("USA")},
+        Nation::Sweden => {emojis.get("Sweden")},
+        Nation::Japan => {emojis.get("Japan")},
+        Nation::Italy => {emojis.get("Italy")},
+        Nation::Czech => {emojis.get("Czech")},
+        Nation::Unknown(_) => {nation.name()},
+    }
+
+
+}
+
+/// How long ago each dataset /marks uses was refreshed, None when it never was.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DatasetAges {
+    pub tank_data: Option<Duration>,
+    pub economics: Option<Duration>,
+    pub server_stats: Option<Duration>,
+}
+
+impl DatasetAges {
+    fn footer(&self) -> String {
+        let mut footer = String::from("Powered by Tomato.gg");
+        for (name, age) in [
+            ("MoE", self.tank_data),
+            ("Economics", self.economics),
+            ("Server stats", self.server_stats),
+        ] {
+            if let Some(age) = age {
+                footer += &format!(" • {} {}", name, format_age(age));
+            }
+        }
+        footer
+    }
+}
+
+fn format_age(age: Duration) -> String {
+    let minutes = age.as_secs() / 60;
+    match minutes {
+        0 => String::from("just now"),
+        1..=59 => format!("{}m ago", minutes),
+        60..=1439 => format!("{}h ago", minutes / 60),
+        _ => format!("{}d ago", minutes / 1440),
+    }
+}
+
+const NOT_ENOUGH_DATA: &str = "Not enough data yet";
+
+/// Economics and server stats show up some time after a new tank's MoE
+/// data, their fields say so until then.
+pub async fn generate_mark_embed(
+        tank: &Tank, 
+        region: &Region, 
+        tank_economics: Option<&TankEconomics>,
+        recent_tank_stats: Option<&RecentTankStats>,
+        ages: &DatasetAges,
+        emojis: &Emojis,) 
+    -> CreateEmbed {
+    let credits = emojis.get("credits");
+    let economics = match tank_economics {
+        Some(tank_economics) => format!(
+            "Avg. Profit: `{}`{credits}\nAvg. Revenue: `{}`{credits}\nAvg. Ammo Cost: `{}`{credits}\nProfit/Min: `{}`{credits}",
+            tank_economics.avg_profit,
+            tank_economics.avg_earnings,
+            tank_economics.avg_ammo_cost,
+            tank_economics.profit_per_minute
+        ),
+        None => String::from(NOT_ENOUGH_DATA),
+    };
+    let server_stats = match recent_tank_stats {
+        Some(recent_tank_stats) => format!(
+            "WN8: `{}`\nWinRate: `{}%`\nDamage: `{}`\nAssist: `{}`",
+            recent_tank_stats.wn8,
+            recent_tank_stats.winrate,
+            recent_tank_stats.damage,
+            recent_tank_stats.spotting_assist 
+            + recent_tank_stats.tracking_assist,
+        ),
+        None => String::from(NOT_ENOUGH_DATA),
+    };
+
+    CreateEmbed::default().title(format!("{} {}",tank.name,region.name()))
+        .url(format!("https://tomato.gg/tanks/{}/{}",region.name(),tank.id))
+        .description(create_tank_embed_description(tank, emojis))
+        .field("MoE Reqs",
+            format!("100: `{}`\n{}: `{}`\n{}: `{}`\n{}: `{}`",
+            tank.pct_100,
+            emojis.get("mark_3"),tank.pct_95,
+            emojis.get("mark_2"),tank.pct_85,
+            emojis.get("mark_1"),tank.pct_65),true)
+        .field("Mastery(XP)",
+            format!("{}: `{}`\n{}: `{}`\n{}: `{}`\n{}: `{}`",
+            emojis.get("masteryIcon"),tank.ace,
+            emojis.get("firstClassIcon"),tank.first,
+            emojis.get("2ndClassIcon"),tank.second,
+            emojis.get("3rdClassIcon"),tank.third),true)
+        .field("Economics", economics, true)
+        .field("30 Days Stats", server_stats, true)
+        // gray without server stats
+        .color(get_wn8_color(recent_tank_stats.map_or(0, |stats| stats.wn8)))
+        .thumbnail(&tank.images.big_icon)
+        .footer(|f| {
+            f.text(ages.footer());
+            f.icon_url("https://tomato.gg/_next/image?url=%2Ftomato.png&w=48&q=75");
+            f
+        }).to_owned()
+}
@@ -0,0 +1,219 @@
+//! Nation, class and tier of a tank as Tomato.gg sends them.
+//!
+//! Nations and classes keep whatever value they don't recognise in
+//! `Unknown`, so a tank from a newly added nation still loads and the cached
+//! datasets round-trip unchanged.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EnumIter, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Nation {
+    USA,
+    USSR,
+    Germany,
+    France,
+    UK,
+    China,
+    Japan,
+    Czech,
+    Sweden,
+    Poland,
+    Italy,
+    #[strum(disabled)]
+    Unknown(String),
+}
+
+impl Nation {
+    /// The value Tomato.gg uses, e.g. `ussr`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Nation::USA => "usa",
+            Nation::USSR => "ussr",
+            Nation::Germany => "germany",
+            Nation::France => "france",
+            Nation::UK => "uk",
+            Nation::China => "china",
+            Nation::Japan => "japan",
+            Nation::Czech => "czech",
+            Nation::Sweden => "sweden",
+            Nation::Poland => "poland",
+            Nation::Italy => "italy",
+            Nation::Unknown(nation) => nation,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Nation::USA => "USA",
+            Nation::USSR => "USSR",
+            Nation::Germany => "Germany",
+            Nation::France => "France",
+            Nation::UK => "UK",
+            Nation::China => "China",
+            Nation::Japan => "Japan",
+            Nation::Czech => "Czechoslovakia",
+            Nation::Sweden => "Sweden",
+            Nation::Poland => "Poland",
+            Nation::Italy => "Italy",
+            Nation::Unknown(nation) => nation,
+        }
+    }
+}
+
+impl From<&str> for Nation {
+    fn from(nation: &str) -> Nation {
+        Nation::iter()
+            .find(|known| known.as_str() == nation)
+            .unwrap_or_else(|| Nation::Unknown(nation.to_owned()))
+    }
+}
+
+impl From<String> for Nation {
+    fn from(nation: String) -> Nation {
+        Nation::from(nation.as_str())
+    }
+}
+
+impl From<Nation> for String {
+    fn from(nation: Nation) -> String {
+        nation.as_str().to_owned()
+    }
+}
+
+impl fmt::Display for Nation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EnumIter, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum TankClass {
+    LT,
+    MT,
+    HT,
+    TD,
+    SPG,
+    #[strum(disabled)]
+    Unknown(String),
+}
+
+impl TankClass {
+    /// The value Tomato.gg uses, e.g. `MT`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            TankClass::LT => "LT",
+            TankClass::MT => "MT",
+            TankClass::HT => "HT",
+            TankClass::TD => "TD",
+            TankClass::SPG => "SPG",
+            TankClass::Unknown(class) => class,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            TankClass::LT => "Light Tank",
+            TankClass::MT => "Medium Tank",
+            TankClass::HT => "Heavy Tank",
+            TankClass::TD => "Tank Destroyer",
+            TankClass::SPG => "SPG",
+            TankClass::Unknown(class) => class,
+        }
+    }
+}
+
+impl From<&str> for TankClass {
+    fn from(class: &str) -> TankClass {
+        TankClass::iter()
+            .find(|known| known.as_str() == class)
+            .unwrap_or_else(|| TankClass::Unknown(class.to_owned()))
+    }
+}
+
+impl From<String> for TankClass {
+    fn from(class: String) -> TankClass {
+        TankClass::from(class.as_str())
+    }
+}
+
+impl From<TankClass> for String {
+    fn from(class: TankClass) -> String {
+        class.as_str().to_owned()
+    }
+}
+
+impl fmt::Display for TankClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+// poise can't derive ChoiceParameter for enums with an Unknown(String), so
+// the choices are the known variants in iteration order
+macro_rules! known_variant_choices {
+    ($enum_:ident) => {
+        impl $enum_ {
+            /// The known variant a choice parameter's value (its index) picks.
+            pub fn from_choice(value: &poise::serenity_prelude::json::Value) -> Option<$enum_> {
+                $enum_::iter().nth(value.as_u64()? as usize)
+            }
+        }
+
+        #[poise::async_trait]
+        impl poise::SlashArgument for $enum_ {
+            async fn extract(
+                _: &poise::serenity_prelude::Context,
+                _: poise::ApplicationCommandOrAutocompleteInteraction<'_>,
+                value: &poise::serenity_prelude::json::Value,
+            ) -> Result<Self, poise::SlashArgError> {
+                $enum_::from_choice(value).ok_or(poise::SlashArgError::CommandStructureMismatch(
+                    "out of bounds choice key",
+                ))
+            }
+
+            fn create(builder: &mut poise::serenity_prelude::CreateApplicationCommandOption) {
+                builder.kind(poise::serenity_prelude::CommandOptionType::Integer);
+            }
+
+            fn choices() -> Vec<poise::CommandParameterChoice> {
+                $enum_::iter()
+                    .map(|variant| poise::CommandParameterChoice {
+                        name: variant.name().to_owned(),
+                        localizations: Default::default(),
+                    })
+                    .collect()
+            }
+        }
+    };
+}
+
+known_variant_choices!(Nation);
+known_variant_choices!(TankClass);
+
+/// Displays as a Roman numeral, `X` for tier 10.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Tier(pub u32);
+
+impl Tier {
+    pub fn roman(&self) -> Option<&'static str> {
+        const NUMERALS: [&str; 11] = [
+            "I", "II", "III", "IV", "V", "VI", "VII", "VIII", "IX", "X", "XI",
+        ];
+        NUMERALS.get(self.0.checked_sub(1)? as usize).copied()
+    }
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.roman() {
+            Some(numeral) => f.write_str(numeral),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
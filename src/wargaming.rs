@@ -0,0 +1,69 @@
+use crate::errors::WgApiError;
+use serde::Deserialize;
+
+/// Every WG endpoint wraps its payload like this and reports errors with a
+/// 200 and `status: "error"`, so check the status before touching `data`.
+#[derive(Deserialize)]
+pub struct WgResponse<T> {
+    status: String,
+    #[serde(default)]
+    error: Option<WgErrorBody>,
+    #[serde(default = "Option::default")]
+    data: Option<T>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WgErrorBody {
+    pub code: u32,
+    pub message: String,
+    #[serde(default)]
+    pub field: Option<String>,
+}
+
+impl<T: Default> WgResponse<T> {
+    pub fn into_result(self) -> Result<T, WgApiError> {
+        if self.status == "ok" {
+            return Ok(self.data.unwrap_or_default());
+        }
+
+        Err(match self.error {
+            Some(error) => error.into(),
+            None => WgApiError::Other {
+                code: 0,
+                message: format!("status {}", self.status),
+            },
+        })
+    }
+}
+
+impl From<WgErrorBody> for WgApiError {
+    fn from(body: WgErrorBody) -> Self {
+        // the %FIELD% errors come back as ex. ACCOUNT_ID_LIST_LIMIT_EXCEEDED
+        // with "field": "account_id" alongside
+        match (body.message.as_str(), body.field) {
+            ("INVALID_SEARCH", _) => WgApiError::InvalidSearch,
+            ("NOT_ENOUGH_SEARCH_LENGTH", _) => WgApiError::NotEnoughSearchLength,
+            ("REQUEST_LIMIT_EXCEEDED", _) => WgApiError::RequestLimitExceeded,
+            ("SOURCE_NOT_AVAILABLE", _) => WgApiError::SourceNotAvailable,
+            ("INVALID_APPLICATION_ID", _) => WgApiError::InvalidApplicationId,
+            ("APPLICATION_IS_BLOCKED", _) => WgApiError::ApplicationIsBlocked,
+            ("INVALID_IP_ADDRESS", _) => WgApiError::InvalidIpAddress,
+            (message, Some(field)) if message.ends_with("_LIST_LIMIT_EXCEEDED") => {
+                WgApiError::ListLimitExceeded(field)
+            }
+            (message, Some(field)) if message.ends_with("_NOT_SPECIFIED") => {
+                WgApiError::FieldNotSpecified(field)
+            }
+            (message, Some(field)) if message.ends_with("_NOT_FOUND") => {
+                WgApiError::FieldNotFound(field)
+            }
+            (message, Some(field)) if message.starts_with("INVALID_") => {
+                WgApiError::InvalidField(field)
+            }
+            _ => WgApiError::Other {
+                code: body.code,
+                message: body.message.clone(),
+            },
+        }
+    }
+}
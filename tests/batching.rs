@@ -0,0 +1,128 @@
+mod common;
+
+use common::{json, MockUpstream, ACCOUNT_ID};
+use std::time::Duration;
+use tomatobot::player_stats::player::{fetch_candidate, fetch_clan_info, Player};
+use tomatobot::Region;
+use wiremock::Request;
+
+#[tokio::test]
+async fn clan_info_lookups_share_one_request() {
+    let mut upstream = MockUpstream::start().await;
+    upstream.config.wargaming.batch_window_ms = 50;
+    upstream.mount_all().await;
+    let wargaming = upstream.wargaming();
+
+    let (first, second, third) = tokio::join!(
+        fetch_clan_info(&wargaming, &Region::NA, &ACCOUNT_ID),
+        fetch_clan_info(&wargaming, &Region::NA, &1012345679),
+        fetch_clan_info(&wargaming, &Region::NA, &1012345680),
+    );
+
+    // only the fixture's account is in a clan
+    assert!(first.unwrap().is_some());
+    assert!(second.unwrap().is_none());
+    assert!(third.unwrap().is_none());
+    let requests = upstream.server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        query_value(&requests[0], "account_id"),
+        "1012345678,1012345679,1012345680"
+    );
+}
+
+#[tokio::test]
+async fn candidate_lookups_share_one_request() {
+    let mut upstream = MockUpstream::start().await;
+    upstream.config.wargaming.batch_window_ms = 50;
+    upstream
+        .mount("/na/wot/account/info/", json("account_info"))
+        .await;
+    let wargaming = upstream.wargaming();
+
+    let candidate = |account_id| {
+        let player = Player {
+            nickname: String::from("tomato_enjoyer"),
+            account_id,
+        };
+        fetch_candidate(&wargaming, Region::NA, player)
+    };
+    let (first, second) = tokio::join!(candidate(ACCOUNT_ID), candidate(1012345679));
+
+    assert_eq!(first.battles, Some(23814));
+    assert_eq!(second.battles, None);
+    let requests = upstream.server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        query_value(&requests[0], "account_id"),
+        "1012345678,1012345679"
+    );
+}
+
+#[tokio::test]
+async fn lookups_for_different_app_ids_stay_apart() {
+    let mut upstream = MockUpstream::start().await;
+    upstream.config.wargaming.batch_window_ms = 50;
+    upstream.mount_all().await;
+    let wargaming = upstream.wargaming();
+
+    let (first, second) = tokio::join!(
+        wargaming.get_account_batched("clans/accountinfo", "app_a", &Region::NA, ACCOUNT_ID),
+        wargaming.get_account_batched("clans/accountinfo", "app_b", &Region::NA, 1012345679),
+    );
+
+    assert!(first.unwrap().is_some());
+    assert!(second.unwrap().is_none());
+    let requests = upstream.server.received_requests().await.unwrap();
+    let mut sent: Vec<(String, String)> = requests
+        .iter()
+        .map(|request| {
+            (
+                query_value(request, "application_id"),
+                query_value(request, "account_id"),
+            )
+        })
+        .collect();
+    sent.sort();
+    assert_eq!(
+        sent,
+        [
+            (String::from("app_a"), ACCOUNT_ID.to_string()),
+            (String::from("app_b"), String::from("1012345679")),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn full_batch_goes_out_without_waiting() {
+    let mut upstream = MockUpstream::start().await;
+    // the window alone would outlast the test's timeout
+    upstream.config.wargaming.batch_window_ms = 60_000;
+    upstream.config.wargaming.max_batch_size = 2;
+    upstream.mount_all().await;
+    let wargaming = upstream.wargaming();
+
+    let lookups = async {
+        tokio::join!(
+            fetch_clan_info(&wargaming, &Region::NA, &ACCOUNT_ID),
+            fetch_clan_info(&wargaming, &Region::NA, &1012345679),
+        )
+    };
+    let (first, second) = tokio::time::timeout(Duration::from_secs(5), lookups)
+        .await
+        .unwrap();
+
+    assert!(first.is_ok());
+    assert!(second.is_ok());
+    let requests = upstream.server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+}
+
+fn query_value(request: &Request, key: &str) -> String {
+    request
+        .url
+        .query_pairs()
+        .find(|(name, _)| name == key)
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_default()
+}
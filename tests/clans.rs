@@ -0,0 +1,169 @@
+mod common;
+
+use common::{json, MockUpstream, CLAN_ID};
+use tomatobot::clans::{
+    fetch_all_clan, fetch_clan_id, fetch_clan_rating, fetch_global_map, fetch_tomato_clan,
+};
+use tomatobot::errors::{Upstream, UpstreamErrorKind, WgApiError};
+use tomatobot::Region;
+use wiremock::ResponseTemplate;
+
+#[tokio::test]
+async fn clan_id() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let clan_id = fetch_clan_id(&upstream.wargaming(), Region::NA, "RELIC")
+        .await
+        .unwrap();
+    assert_eq!(clan_id, Some(CLAN_ID));
+}
+
+#[tokio::test]
+async fn clan_id_empty_search() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/na/wot/clans/list/", json("account_list_empty"))
+        .await;
+
+    let clan_id = fetch_clan_id(&upstream.wargaming(), Region::NA, "NOPE")
+        .await
+        .unwrap();
+    assert_eq!(clan_id, None);
+}
+
+#[tokio::test]
+async fn clan_id_wargaming_error() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/na/wot/clans/list/", json("wg_error_invalid_search"))
+        .await;
+
+    let error = fetch_clan_id(&upstream.wargaming(), Region::NA, "a!")
+        .await
+        .unwrap_err();
+    assert_eq!(error.upstream, Upstream::Wargaming);
+    assert_eq!(error.endpoint, "clans/list");
+    assert_eq!(error.region, Some(Region::NA));
+    assert_eq!(
+        error.kind,
+        UpstreamErrorKind::Api(WgApiError::InvalidSearch)
+    );
+    assert_eq!(
+        error.user_message(),
+        "Invalid search, check the name and try again"
+    );
+}
+
+#[tokio::test]
+async fn global_map() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let global = fetch_global_map(&upstream.wargaming(), Region::NA, CLAN_ID)
+        .await
+        .unwrap();
+    assert!(global.is_some());
+
+    // clans that never played global map aren't in the response
+    let missing = fetch_global_map(&upstream.wargaming(), Region::NA, 1)
+        .await
+        .unwrap();
+    assert!(missing.is_none());
+}
+
+#[tokio::test]
+async fn clan_rating() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let rating = fetch_clan_rating(&upstream.wargaming(), Region::NA, CLAN_ID)
+        .await
+        .unwrap();
+    assert!(rating.is_some());
+}
+
+#[tokio::test]
+async fn tomato_clan() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let clan = fetch_tomato_clan(&upstream.tomato(), Region::NA, CLAN_ID)
+        .await
+        .unwrap();
+    assert_eq!(clan.tag, "RELIC");
+    assert_eq!(clan.name, "Relentless");
+    assert_eq!(clan.color, "#b3261e");
+}
+
+#[tokio::test]
+async fn all_clan_data() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let clan = fetch_all_clan(
+        &upstream.tomato(),
+        &upstream.wargaming(),
+        Region::NA,
+        CLAN_ID,
+    )
+    .await
+    .unwrap();
+    assert!(clan.rating.is_some());
+    assert!(clan.global.is_some());
+    assert_eq!(clan.tomato.tag, "RELIC");
+}
+
+#[tokio::test]
+async fn all_clan_data_rate_limited() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/na/wot/clanratings/clans/", json("wg_error_request_limit"))
+        .await;
+    upstream
+        .mount("/na/wot/globalmap/claninfo/", json("globalmap_claninfo"))
+        .await;
+    upstream
+        .mount(&format!("/api/clan/com/{}", CLAN_ID), json("tomato_clan"))
+        .await;
+
+    let result = fetch_all_clan(
+        &upstream.tomato(),
+        &upstream.wargaming(),
+        Region::NA,
+        CLAN_ID,
+    )
+    .await;
+    let error = result.err().unwrap();
+    assert_eq!(error.endpoint, "clanratings/clans");
+}
+
+#[tokio::test]
+async fn all_clan_data_tomato_down() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount(
+            &format!("/api/clan/com/{}", CLAN_ID),
+            ResponseTemplate::new(502),
+        )
+        .await;
+    upstream
+        .mount("/na/wot/clanratings/clans/", json("clanratings"))
+        .await;
+    upstream
+        .mount("/na/wot/globalmap/claninfo/", json("globalmap_claninfo"))
+        .await;
+
+    let result = fetch_all_clan(
+        &upstream.tomato(),
+        &upstream.wargaming(),
+        Region::NA,
+        CLAN_ID,
+    )
+    .await;
+    let error = result.err().unwrap();
+    assert_eq!(error.upstream, Upstream::Tomato);
+    assert_eq!(error.endpoint, "clan");
+    assert_eq!(error.status.map(|s| s.as_u16()), Some(502));
+    assert_eq!(error.kind, UpstreamErrorKind::Unavailable);
+}
@@ -0,0 +1,109 @@
+//! tomato-cli run against the mock server, pointed there through the same
+//! environment overrides the bot reads.
+
+mod common;
+
+use common::MockUpstream;
+use serde_json::Value;
+use std::process::Output;
+use tokio::process::Command;
+
+async fn tomato_cli(upstream: &MockUpstream, args: &[&str]) -> Output {
+    let uri = upstream.server.uri();
+    Command::new(env!("CARGO_BIN_EXE_tomato-cli"))
+        .args(args)
+        .env("TOMATOBOT_CONFIG", "does-not-exist.toml")
+        .env("TOMATO_API_BASE", format!("{}/api-v2", uri))
+        .env("TOMATO_LEGACY_API_BASE", format!("{}/api", uri))
+        .env("WG_HOST_NA", format!("{}/na", uri))
+        .env("WG_HOST_EU", format!("{}/eu", uri))
+        .env("WG_HOST_ASIA", format!("{}/asia", uri))
+        .output()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn stats_json() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let output = tomato_cli(
+        &upstream,
+        &["--json", "stats", "tomato_enjoyer", "--region", "NA"],
+    )
+    .await;
+    assert!(output.status.success());
+    let embed: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(embed["title"], "tomato_enjoyer's Stats");
+    assert_eq!(embed["description"], "**XO at [RELIC]**");
+}
+
+#[tokio::test]
+async fn marks_text() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let output = tomato_cli(&upstream, &["marks", "obj 140"]).await;
+    assert!(output.status.success());
+    let text = String::from_utf8(output.stdout).unwrap();
+    assert!(text.starts_with("Obj. 140 NA\nhttps://tomato.gg/tanks/NA/16897\n"));
+    assert!(text.contains("\nMoE Reqs\n  100: 5439\n  3 Marks: 4280\n"));
+}
+
+#[tokio::test]
+async fn bulk_stats_keep_going_past_missing_players() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    // the account list fixture answers every search with tomato_enjoyer,
+    // which the exact match throws away for any other name
+    let output = tomato_cli(
+        &upstream,
+        &[
+            "--json",
+            "stats",
+            "nobody_here",
+            "tomato_enjoyer",
+            "--region",
+            "NA",
+        ],
+    )
+    .await;
+    assert_eq!(output.status.code(), Some(2));
+    let embeds: Vec<Value> = serde_json::Deserializer::from_slice(&output.stdout)
+        .into_iter()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert_eq!(embeds.len(), 1);
+    assert_eq!(embeds[0]["title"], "tomato_enjoyer's Stats");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap().trim(),
+        "nobody_here: No player found with that name"
+    );
+}
+
+#[tokio::test]
+async fn compare_json() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let output = tomato_cli(
+        &upstream,
+        &[
+            "--json",
+            "compare",
+            "tomato_enjoyer",
+            "tomato_enjoyer",
+            "--region",
+            "NA",
+            "--period",
+            "R3DAYS",
+        ],
+    )
+    .await;
+    assert!(output.status.success());
+    let embed: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(embed["title"], "tomato_enjoyer vs tomato_enjoyer");
+    assert_eq!(embed["fields"][6]["name"], "Tanks Both Played (3 Days)");
+}
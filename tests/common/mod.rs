@@ -0,0 +1,128 @@
+//! Shared setup for the offline tests: a wiremock server standing in for
+//! both Tomato.gg and Wargaming, and the recorded payloads in `fixtures/`.
+
+#![allow(dead_code)]
+
+use tomatobot::client::{TomatoClient, UpstreamClient, WargamingClient};
+use tomatobot::config::Config;
+use tomatobot::emoji::Emojis;
+use tomatobot::metrics::Metrics;
+use tomatobot::scheduler::SchedulerStatus;
+use tomatobot::service::TankCaches;
+use tomatobot::StatsService;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+pub const ACCOUNT_ID: u32 = 1012345678;
+pub const CLAN_ID: u32 = 1000012345;
+
+pub fn fixture(name: &str) -> String {
+    let path = format!(
+        "{}/tests/fixtures/{}.json",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("reading {}: {}", path, e))
+}
+
+/// The emojis from `config.example.toml`, what the embeds look like on the
+/// server they were made for.
+pub fn example_emojis() -> Emojis {
+    let path = format!("{}/config.example.toml", env!("CARGO_MANIFEST_DIR"));
+    let config = Config::from_file(std::path::Path::new(&path)).unwrap();
+    Emojis::from_config(&config.emojis)
+}
+
+pub fn json(name: &str) -> ResponseTemplate {
+    ResponseTemplate::new(200).set_body_raw(fixture(name), "application/json")
+}
+
+/// Tomato lives under /api-v2 (legacy /api) and each WG region under its
+/// own prefix (/na, /eu, /asia), all on the same mock server.
+pub struct MockUpstream {
+    pub server: MockServer,
+    pub config: Config,
+}
+
+impl MockUpstream {
+    pub async fn start() -> MockUpstream {
+        let server = MockServer::start().await;
+        let uri = server.uri();
+
+        let mut config = Config::default();
+        config.http.max_retries = 1;
+        config.http.retry_base_delay_ms = 1;
+        config.http.timeout_secs = 5;
+        config.tomato.api_base = format!("{}/api-v2", uri);
+        config.tomato.legacy_api_base = format!("{}/api", uri);
+        config.wargaming.hosts.na = format!("{}/na", uri);
+        config.wargaming.hosts.eu = format!("{}/eu", uri);
+        config.wargaming.hosts.asia = format!("{}/asia", uri);
+        config.wargaming.batch_window_ms = 1;
+
+        MockUpstream { server, config }
+    }
+
+    pub async fn mount(&self, route: &str, response: ResponseTemplate) {
+        Mock::given(method("GET"))
+            .and(path(route))
+            .respond_with(response)
+            .mount(&self.server)
+            .await;
+    }
+
+    /// Every endpoint answering with the happy path fixtures, NA only.
+    pub async fn mount_all(&self) {
+        self.mount("/api-v2/moe/com", json("moe")).await;
+        self.mount("/api-v2/mastery/com", json("mastery")).await;
+        self.mount("/api-v2/tank-economics", json("tank_economics"))
+            .await;
+        self.mount(
+            "/api-v2/all-tanks-server-stats-wr-range/com/0/100",
+            json("all_tanks_server_stats"),
+        )
+        .await;
+        self.mount(
+            &format!("/api-v2/overall/com/{}", ACCOUNT_ID),
+            json("overall"),
+        )
+        .await;
+        self.mount(
+            &format!("/api-v2/recents/com/{}", ACCOUNT_ID),
+            json("recents"),
+        )
+        .await;
+        self.mount(&format!("/api/clan/com/{}", CLAN_ID), json("tomato_clan"))
+            .await;
+        self.mount("/na/wot/account/list/", json("account_list"))
+            .await;
+        self.mount("/na/wot/clans/accountinfo/", json("clans_accountinfo"))
+            .await;
+        self.mount("/na/wot/clans/list/", json("clans_list")).await;
+        self.mount("/na/wot/globalmap/claninfo/", json("globalmap_claninfo"))
+            .await;
+        self.mount("/na/wot/clanratings/clans/", json("clanratings"))
+            .await;
+    }
+
+    fn http(&self) -> UpstreamClient {
+        UpstreamClient::new(&self.config.http, Metrics::new()).unwrap()
+    }
+
+    pub fn tomato(&self) -> TomatoClient {
+        TomatoClient::new(self.http(), &self.config)
+    }
+
+    pub fn wargaming(&self) -> WargamingClient {
+        WargamingClient::new(self.http(), &self.config)
+    }
+
+    pub fn service(&self, caches: TankCaches) -> StatsService {
+        StatsService::new(
+            self.tomato(),
+            self.wargaming(),
+            caches,
+            SchedulerStatus::default(),
+        )
+    }
+}
@@ -0,0 +1,238 @@
+//! Snapshots of every embed built from the fixtures. Formatting changes show
+//! up as diffs under `snapshots/`; review them with `cargo insta review`.
+
+mod common;
+
+use common::{example_emojis, MockUpstream, CLAN_ID};
+use insta::assert_json_snapshot;
+use poise::serenity_prelude::CreateEmbed;
+use std::time::Duration;
+use strum::IntoEnumIterator;
+use tomatobot::clans::{fetch_all_clan, generate_clan_embed};
+use tomatobot::embed::to_json;
+use tomatobot::emoji::Emojis;
+use tomatobot::player_stats::compare::generate_compare_embed;
+use tomatobot::player_stats::player::{
+    generate_main_stat_embed, generate_period_embed, Period, PlayerData,
+};
+use tomatobot::player_stats::TomatoStats;
+use tomatobot::service::TankCaches;
+use tomatobot::tanks::{
+    fetch_recent_tank_stats, fetch_tank_data, fetch_tank_economics, generate_mark_embed,
+    DatasetAges,
+};
+use tomatobot::Region;
+
+const AGES: DatasetAges = DatasetAges {
+    tank_data: Some(Duration::from_secs(2 * 60 * 60)),
+    economics: Some(Duration::from_secs(3 * 24 * 60 * 60)),
+    server_stats: Some(Duration::from_secs(20 * 60)),
+};
+
+/// tomato_enjoyer with both passes done, so clan stats are filled in too.
+async fn player_data(upstream: &MockUpstream) -> PlayerData {
+    let service = upstream.service(TankCaches::default());
+    let (region, player) = service
+        .find_player("tomato_enjoyer", Some(Region::NA))
+        .await
+        .unwrap()
+        .found()
+        .unwrap();
+    let mut data = service.player_data(region, player).await;
+    service.refresh_player_data(&mut data).await;
+    data
+}
+
+#[tokio::test]
+async fn main_stat_embed() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let data = player_data(&upstream).await;
+
+    let embed = generate_main_stat_embed(&data).await.unwrap();
+    assert_json_snapshot!(to_json(&embed));
+}
+
+#[tokio::test]
+async fn period_embeds() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let data = player_data(&upstream).await;
+
+    for period in Period::iter() {
+        let embed = generate_period_embed(&data, period).await.unwrap();
+        assert_json_snapshot!(format!("period_embed_{}", period.name()), to_json(&embed));
+    }
+}
+
+/// The fixture player again, renamed and with different numbers, so every
+/// row of /compare has a winner and only some tanks are shared.
+async fn rival_data(upstream: &MockUpstream) -> PlayerData {
+    let mut data = player_data(upstream).await;
+    data.player.nickname = String::from("tomato_rival");
+    if let TomatoStats::Found(overall) = &mut data.overall {
+        overall.battles += 1000;
+        overall.wn8 -= 150;
+        overall.win_rate = 54.5;
+        overall.tier = 8.6;
+        overall.dpg += 40;
+    }
+    if let TomatoStats::Found(recents) = &mut data.recents {
+        let days = &mut recents.recent3days;
+        days.overall.battles += 3;
+        days.overall.wn8 += 300;
+        days.overall.winrate = 60.0;
+        days.overall.tier = 9.0;
+        days.overall.dpg -= 100;
+        days.tank_stats.remove(0);
+        for tank in &mut days.tank_stats {
+            tank.wn8 += 50;
+            tank.battles += 3;
+        }
+    }
+    data
+}
+
+#[tokio::test]
+async fn compare_embed() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let data = player_data(&upstream).await;
+    let rival = rival_data(&upstream).await;
+
+    let embed = generate_compare_embed(&data, &rival, Period::R3DAYS)
+        .await
+        .unwrap();
+    assert_json_snapshot!(to_json(&embed));
+}
+
+#[tokio::test]
+async fn compare_embed_ties_stay_plain() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let data = player_data(&upstream).await;
+
+    let embed = generate_compare_embed(&data, &data, Period::R3DAYS)
+        .await
+        .unwrap();
+    let json = to_json(&embed);
+    for field in json["fields"].as_array().unwrap() {
+        let value = field["value"].as_str().unwrap();
+        assert!(!value.contains("**"), "{}", value);
+    }
+}
+
+#[tokio::test]
+async fn compare_embed_without_recents() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let data = player_data(&upstream).await;
+    let mut rival = rival_data(&upstream).await;
+    rival.recents = TomatoStats::Private;
+
+    let error = generate_compare_embed(&data, &rival, Period::R3DAYS)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(
+        error.to_string(),
+        "Missing Required Recents Data for tomato_rival"
+    );
+}
+
+#[tokio::test]
+async fn mark_embed() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let tomato = upstream.tomato();
+
+    let tanks = fetch_tank_data(&tomato, &Region::NA).await.unwrap();
+    let economics = fetch_tank_economics(&tomato).await.unwrap();
+    let recent_stats = fetch_recent_tank_stats(&tomato, &Region::NA).await.unwrap();
+
+    for tank in &tanks {
+        let tank_economics = economics.iter().find(|t| t.id == tank.id).unwrap();
+        let tank_stats = recent_stats.iter().find(|t| t.tank_id == tank.id).unwrap();
+        let embed = generate_mark_embed(
+            tank,
+            &Region::NA,
+            Some(tank_economics),
+            Some(tank_stats),
+            &AGES,
+            &example_emojis(),
+        )
+        .await;
+        assert_json_snapshot!(format!("mark_embed_{}", tank.id), to_json(&embed));
+    }
+}
+
+#[tokio::test]
+async fn mark_embed_without_stats() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let tanks = fetch_tank_data(&upstream.tomato(), &Region::NA)
+        .await
+        .unwrap();
+    // a tank that was just added, only its MoE data has loaded so far
+    let ages = DatasetAges {
+        tank_data: Some(Duration::from_secs(30)),
+        ..DatasetAges::default()
+    };
+    let embed =
+        generate_mark_embed(&tanks[0], &Region::NA, None, None, &ages, &example_emojis()).await;
+    assert_json_snapshot!(to_json(&embed));
+}
+
+#[tokio::test]
+async fn mark_embed_without_emojis() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let tomato = upstream.tomato();
+
+    let tanks = fetch_tank_data(&tomato, &Region::NA).await.unwrap();
+    let economics = fetch_tank_economics(&tomato).await.unwrap();
+    // the premium Kunze, so the premium class fallback shows up too
+    let tank = tanks.iter().find(|t| t.id == 64817).unwrap();
+    let embed = generate_mark_embed(
+        tank,
+        &Region::NA,
+        economics.iter().find(|t| t.id == tank.id),
+        None,
+        &AGES,
+        &Emojis::default(),
+    )
+    .await;
+    assert_json_snapshot!(to_json(&embed));
+}
+
+#[tokio::test]
+async fn clan_embed() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let clan = fetch_all_clan(
+        &upstream.tomato(),
+        &upstream.wargaming(),
+        Region::NA,
+        CLAN_ID,
+    )
+    .await
+    .unwrap();
+    let embed = generate_clan_embed(&clan).await;
+    assert_json_snapshot!(to_json(&embed));
+}
+
+#[test]
+fn to_json_sorts_keys() {
+    let mut embed = CreateEmbed::default();
+    embed
+        .title("title")
+        .field("b", "1", true)
+        .footer(|f| f.text("footer").icon_url("https://example.com"));
+
+    let json = serde_json::to_string(&to_json(&embed)).unwrap();
+    assert!(json.find("\"fields\"").unwrap() < json.find("\"footer\"").unwrap());
+    assert!(json.find("\"inline\"").unwrap() < json.find("\"name\"").unwrap());
+    assert!(json.find("\"icon_url\"").unwrap() < json.find("\"text\"").unwrap());
+}
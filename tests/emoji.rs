@@ -0,0 +1,76 @@
+use serde_json::json;
+use tomatobot::config::EmojiConfig;
+use tomatobot::emoji::{fetch_application_emojis, Emojis};
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+fn configured(ids: &[(&str, u64)]) -> Emojis {
+    Emojis::from_config(&EmojiConfig {
+        discover: true,
+        ids: ids
+            .iter()
+            .map(|(name, id)| (name.to_string(), *id))
+            .collect(),
+    })
+}
+
+#[test]
+fn configured_ids_render_as_tags() {
+    let emojis = configured(&[("mark_3", 42)]);
+    assert_eq!(emojis.get("mark_3"), "<:mark_3:42>");
+}
+
+#[test]
+fn missing_emojis_fall_back() {
+    let emojis = Emojis::default();
+    assert_eq!(emojis.get("mark_3"), "3 Marks");
+    assert_eq!(emojis.get("Germany"), "🇩🇪");
+    assert_eq!(emojis.get("premHT"), "Premium HT");
+    // not one the embeds use, nothing better to show than the name
+    assert_eq!(emojis.get("tomato"), "tomato");
+}
+
+#[tokio::test]
+async fn discovers_application_emojis() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/applications/1234/emojis"))
+        .and(header("authorization", "Bot secret"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "items": [
+                { "id": "1", "name": "mark_3", "animated": false },
+                { "id": "2", "name": "mark_2", "animated": true },
+                { "id": "3", "name": "credits" },
+                { "id": "4", "name": "pepe" },
+            ]
+        })))
+        .mount(&server)
+        .await;
+
+    let found = fetch_application_emojis(&reqwest::Client::new(), &server.uri(), "secret", 1234)
+        .await
+        .unwrap();
+    let mut emojis = configured(&[("mark_3", 42)]);
+    emojis.discover(found);
+
+    // configured IDs win over discovered ones
+    assert_eq!(emojis.get("mark_3"), "<:mark_3:42>");
+    assert_eq!(emojis.get("mark_2"), "<a:mark_2:2>");
+    assert_eq!(emojis.get("credits"), "<:credits:3>");
+    assert_eq!(emojis.get("pepe"), "pepe");
+}
+
+#[tokio::test]
+async fn discovery_reports_http_errors() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(401))
+        .mount(&server)
+        .await;
+
+    assert!(
+        fetch_application_emojis(&reqwest::Client::new(), &server.uri(), "bad", 1234)
+            .await
+            .is_err()
+    );
+}
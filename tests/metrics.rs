@@ -0,0 +1,81 @@
+use std::time::{Duration, SystemTime};
+use tomatobot::metrics::Metrics;
+use tomatobot::scheduler::SchedulerStatus;
+use tomatobot::store::Dataset;
+use tomatobot::Region;
+
+fn sample(rendered: &str, name: &str) -> Option<f64> {
+    rendered
+        .lines()
+        .find(|line| line.starts_with(name))
+        .and_then(|line| line.rsplit(' ').next())
+        .map(|value| value.parse().unwrap())
+}
+
+#[test]
+fn render_exposes_commands_upstreams_and_dataset_ages() {
+    let metrics = Metrics::new();
+    metrics.command_invoked("stats");
+    metrics.command_invoked("stats");
+    metrics.command_failed("stats");
+    metrics.observe_upstream(
+        "wargaming",
+        "account/list",
+        "200",
+        Duration::from_millis(80),
+    );
+    let collector = metrics.collector_guard();
+
+    let status = SchedulerStatus::default();
+    let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+    status.seed(Dataset::TankData, Some(Region::EU), an_hour_ago);
+    status.seed(Dataset::TankEconomics, None, an_hour_ago);
+
+    let rendered = metrics.render(&status);
+    assert_eq!(
+        sample(
+            &rendered,
+            r#"tomatobot_command_invocations_total{command="stats"}"#
+        ),
+        Some(2.0)
+    );
+    assert_eq!(
+        sample(
+            &rendered,
+            r#"tomatobot_command_failures_total{command="stats"}"#
+        ),
+        Some(1.0)
+    );
+    assert_eq!(
+        sample(
+            &rendered,
+            r#"tomatobot_upstream_request_duration_seconds_count{endpoint="account/list",status="200",upstream="wargaming"}"#
+        ),
+        Some(1.0)
+    );
+    assert_eq!(
+        sample(&rendered, "tomatobot_active_component_collectors"),
+        Some(1.0)
+    );
+    let age = sample(
+        &rendered,
+        r#"tomatobot_dataset_age_seconds{dataset="tank_data",region="EU"}"#,
+    )
+    .unwrap();
+    assert!((3600.0..3660.0).contains(&age));
+    // economics isn't split by region
+    assert!(sample(
+        &rendered,
+        r#"tomatobot_dataset_age_seconds{dataset="tank_economics",region=""}"#
+    )
+    .is_some());
+    // never refreshed, so there's no age to report
+    assert!(!rendered.contains(r#"dataset="recent_tank_stats""#));
+
+    drop(collector);
+    let rendered = metrics.render(&status);
+    assert_eq!(
+        sample(&rendered, "tomatobot_active_component_collectors"),
+        Some(0.0)
+    );
+}
@@ -0,0 +1,396 @@
+mod common;
+
+use common::{fixture, json, MockUpstream, ACCOUNT_ID, CLAN_ID};
+use serde_json::json;
+use tomatobot::errors::{Upstream, UpstreamErrorKind, WgApiError};
+use tomatobot::player_stats::overall::{fetch_overall_data, OverallData};
+use tomatobot::player_stats::player::{
+    fetch_clan_info, fetch_user_id, find_user_server, parse_suggestion, suggestion_value, Player,
+};
+use tomatobot::player_stats::recents::{fetch_recent_data, OverallStats, RecentsData};
+use tomatobot::player_stats::TomatoStats;
+use tomatobot::Region;
+use wiremock::ResponseTemplate;
+
+fn player() -> Player {
+    Player {
+        nickname: String::from("tomato_enjoyer"),
+        account_id: ACCOUNT_ID,
+    }
+}
+
+#[test]
+fn null_recent_stats_become_defaults() {
+    let stats: OverallStats = serde_json::from_value(json!({
+        "battles": 0,
+        "wn8": null,
+        "tier": null,
+        "winrate": null,
+        "dpg": null,
+    }))
+    .unwrap();
+    assert_eq!(stats.wn8, 0);
+    assert_eq!(stats.tier, 0.0);
+    assert_eq!(stats.winrate, 0.0);
+    assert_eq!(stats.dpg, 0);
+}
+
+#[test]
+fn battles_are_still_required() {
+    let stats = serde_json::from_value::<OverallStats>(json!({
+        "battles": null,
+        "wn8": 1,
+        "tier": 1.0,
+        "winrate": 1.0,
+        "dpg": 1,
+    }));
+    assert!(stats.is_err());
+}
+
+#[test]
+fn suggestions_keep_their_region() {
+    let value = suggestion_value("tomato_enjoyer", Region::EU);
+    assert_eq!(
+        parse_suggestion(&value),
+        ("tomato_enjoyer", Some(Region::EU))
+    );
+    // typed by hand
+    assert_eq!(parse_suggestion("tomato_enjoyer"), ("tomato_enjoyer", None));
+    assert_eq!(parse_suggestion("tomato:XX"), ("tomato:XX", None));
+}
+
+#[test]
+fn recents_fixture_decodes() {
+    let response: serde_json::Value = serde_json::from_str(&fixture("recents")).unwrap();
+    let recents: RecentsData = serde_json::from_value(response["data"].clone()).unwrap();
+
+    assert_eq!(recents.recent24hr.overall.wn8, 0);
+    assert_eq!(recents.recent3days.tank_stats.len(), 2);
+    // tankStats is missing entirely for 30 days
+    assert!(recents.recent30days.tank_stats.is_empty());
+    assert_eq!(recents.recent1000battles.overall.battles, 1000);
+}
+
+#[tokio::test]
+async fn overall_data() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let overall = fetch_overall_data(&upstream.tomato(), &Region::NA, &player(), true).await;
+    let TomatoStats::Found(overall) = overall else {
+        panic!("overall stats should be found");
+    };
+    assert_eq!(overall.battles, 23814);
+    assert_eq!(overall.wn8, 2734);
+}
+
+#[tokio::test]
+async fn overall_cached_flag_is_sent() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let tomato = upstream.tomato();
+    fetch_overall_data(&tomato, &Region::NA, &player(), true).await;
+    fetch_overall_data(&tomato, &Region::NA, &player(), false).await;
+
+    let queries: Vec<Option<String>> = upstream
+        .server
+        .received_requests()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.url.query().map(str::to_owned))
+        .collect();
+    assert_eq!(queries, vec![Some(String::from("cache=true")), None]);
+}
+
+async fn overall_from(response: ResponseTemplate) -> TomatoStats<OverallData> {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount(&format!("/api-v2/overall/com/{}", ACCOUNT_ID), response)
+        .await;
+    fetch_overall_data(&upstream.tomato(), &Region::NA, &player(), true).await
+}
+
+#[tokio::test]
+async fn overall_not_tracked() {
+    let overall = overall_from(json("overall_not_tracked")).await;
+    assert!(matches!(overall, TomatoStats::NotTracked));
+    let overall = overall_from(ResponseTemplate::new(404)).await;
+    assert!(matches!(overall, TomatoStats::NotTracked));
+}
+
+#[tokio::test]
+async fn overall_private() {
+    let overall = overall_from(json("overall_private")).await;
+    assert!(matches!(overall, TomatoStats::Private));
+    let overall = overall_from(ResponseTemplate::new(403)).await;
+    assert!(matches!(overall, TomatoStats::Private));
+}
+
+#[tokio::test]
+async fn overall_schema_mismatch_keeps_the_path() {
+    let overall = overall_from(json("overall_malformed")).await;
+    let TomatoStats::SchemaMismatch(error) = overall else {
+        panic!("malformed stats should be a schema mismatch");
+    };
+    assert_eq!(error.kind, UpstreamErrorKind::Decode);
+    assert!(
+        error.to_string().contains("data.id: invalid type"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn recent_data() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let recents = fetch_recent_data(&upstream.tomato(), &Region::NA, &player(), false).await;
+    assert_eq!(recents.found().unwrap().recent7days.overall.wn8, 2877);
+}
+
+#[tokio::test]
+async fn recent_data_server_error_is_unavailable() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount(
+            &format!("/api-v2/recents/com/{}", ACCOUNT_ID),
+            ResponseTemplate::new(500),
+        )
+        .await;
+
+    let recents = fetch_recent_data(&upstream.tomato(), &Region::NA, &player(), false).await;
+    let TomatoStats::Unavailable(error) = recents else {
+        panic!("a 500 should be unavailable");
+    };
+    assert_eq!(error.status.map(|s| s.as_u16()), Some(500));
+    assert_eq!(
+        error.user_message(),
+        "Tomato.gg is currently unavailable, try again later"
+    );
+}
+
+#[tokio::test]
+async fn user_id_found() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let player = fetch_user_id(&upstream.wargaming(), "tomato_enjoyer", Region::NA)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(player.account_id, ACCOUNT_ID);
+    assert_eq!(player.nickname, "tomato_enjoyer");
+}
+
+#[tokio::test]
+async fn user_id_ignores_case() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let player = fetch_user_id(&upstream.wargaming(), "Tomato_Enjoyer", Region::NA)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(player.nickname, "tomato_enjoyer");
+    let requests = upstream.server.received_requests().await.unwrap();
+    assert!(requests[0].url.query().unwrap().contains("type=exact"));
+}
+
+#[tokio::test]
+async fn user_id_needs_the_exact_name() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/na/wot/account/list/", json("account_list_prefix"))
+        .await;
+
+    let player = fetch_user_id(&upstream.wargaming(), "tomato_enjoyer", Region::NA)
+        .await
+        .unwrap();
+    assert!(player.is_none());
+}
+
+#[tokio::test]
+async fn user_id_not_found() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/na/wot/account/list/", json("account_list_empty"))
+        .await;
+
+    let player = fetch_user_id(&upstream.wargaming(), "nobody", Region::NA)
+        .await
+        .unwrap();
+    assert!(player.is_none());
+}
+
+#[tokio::test]
+async fn user_id_never_sends_invalid_names() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    // a '#' would cut type=exact off as a fragment, an '&' adds parameters
+    for input in ["tomato_enjoyer#", "tomato_enjoyer&type=startswith", ""] {
+        let player = fetch_user_id(&upstream.wargaming(), input, Region::NA)
+            .await
+            .unwrap();
+        assert!(player.is_none(), "{:?}", input);
+    }
+    let requests = upstream.server.received_requests().await.unwrap();
+    assert!(requests.is_empty());
+}
+
+#[tokio::test]
+async fn user_id_wargaming_error() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/na/wot/account/list/", json("wg_error_invalid_search"))
+        .await;
+
+    let error = fetch_user_id(&upstream.wargaming(), "a", Region::NA)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(error.endpoint, "account/list");
+    assert_eq!(
+        error.kind,
+        UpstreamErrorKind::Api(WgApiError::InvalidSearch)
+    );
+}
+
+#[tokio::test]
+async fn user_server_searches_every_region() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/na/wot/account/list/", json("account_list_empty"))
+        .await;
+    upstream
+        .mount("/eu/wot/account/list/", json("account_list"))
+        .await;
+    upstream
+        .mount("/asia/wot/account/list/", json("account_list_empty"))
+        .await;
+
+    let search = find_user_server(&upstream.wargaming(), "tomato_enjoyer")
+        .await
+        .unwrap();
+    assert_eq!(search.found.len(), 1);
+    assert_eq!(search.found[0].0, Region::EU);
+    assert_eq!(search.found[0].1.account_id, ACCOUNT_ID);
+    assert!(search.failed.is_empty());
+}
+
+#[tokio::test]
+async fn user_server_finds_every_region_with_the_name() {
+    let upstream = MockUpstream::start().await;
+    for region in ["na", "asia"] {
+        upstream
+            .mount(
+                &format!("/{}/wot/account/list/", region),
+                json("account_list"),
+            )
+            .await;
+    }
+    upstream
+        .mount("/eu/wot/account/list/", json("account_list_empty"))
+        .await;
+
+    let search = find_user_server(&upstream.wargaming(), "tomato_enjoyer")
+        .await
+        .unwrap();
+    let regions: Vec<Region> = search.found.iter().map(|(region, _)| *region).collect();
+    assert_eq!(regions, [Region::NA, Region::ASIA]);
+}
+
+#[tokio::test]
+async fn user_server_keeps_matches_past_a_failed_region() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/na/wot/account/list/", json("wg_error_request_limit"))
+        .await;
+    upstream
+        .mount("/eu/wot/account/list/", json("account_list"))
+        .await;
+    upstream
+        .mount("/asia/wot/account/list/", json("account_list_empty"))
+        .await;
+
+    let search = find_user_server(&upstream.wargaming(), "tomato_enjoyer")
+        .await
+        .unwrap();
+    assert_eq!(search.found.len(), 1);
+    assert_eq!(search.found[0].0, Region::EU);
+    assert_eq!(search.failed, [Region::NA]);
+}
+
+#[tokio::test]
+async fn user_server_reports_errors_over_not_found() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/na/wot/account/list/", json("account_list_empty"))
+        .await;
+    upstream
+        .mount("/eu/wot/account/list/", json("wg_error_request_limit"))
+        .await;
+    upstream
+        .mount("/asia/wot/account/list/", json("account_list_empty"))
+        .await;
+
+    let error = find_user_server(&upstream.wargaming(), "tomato_enjoyer")
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(error.kind, UpstreamErrorKind::RateLimited);
+    assert_eq!(error.region, Some(Region::EU));
+    assert_eq!(
+        error.user_message(),
+        "Wargaming is rate limiting, try again in a bit"
+    );
+}
+
+#[tokio::test]
+async fn clan_info_member() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let info = fetch_clan_info(&upstream.wargaming(), &Region::NA, &ACCOUNT_ID)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(info.role, "executive_officer");
+    assert_eq!(info.clan.clan_id, CLAN_ID);
+    assert_eq!(info.clan.tag, "RELIC");
+}
+
+#[tokio::test]
+async fn clan_info_not_in_clan() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount(
+            "/na/wot/clans/accountinfo/",
+            json("clans_accountinfo_no_clan"),
+        )
+        .await;
+
+    let info = fetch_clan_info(&upstream.wargaming(), &Region::NA, &ACCOUNT_ID)
+        .await
+        .unwrap();
+    assert!(info.is_none());
+}
+
+#[tokio::test]
+async fn clan_info_wargaming_error() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/na/wot/clans/accountinfo/", json("wg_error_request_limit"))
+        .await;
+
+    let error = fetch_clan_info(&upstream.wargaming(), &Region::NA, &ACCOUNT_ID)
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(error.upstream, Upstream::Wargaming);
+    assert_eq!(error.endpoint, "clans/accountinfo");
+    assert_eq!(error.kind, UpstreamErrorKind::RateLimited);
+}
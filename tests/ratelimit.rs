@@ -0,0 +1,14 @@
+use std::time::{Duration, Instant};
+use tomatobot::ratelimit::RateLimiter;
+
+#[tokio::test]
+async fn rate_limiter_spaces_out_calls() {
+    let limiter = RateLimiter::new(20, 2);
+    let start = Instant::now();
+
+    // two go right away as the burst, the other four wait 50ms each
+    for _ in 0..6 {
+        limiter.acquire().await;
+    }
+    assert!(start.elapsed() >= Duration::from_millis(190));
+}
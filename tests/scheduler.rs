@@ -0,0 +1,22 @@
+use std::time::Duration;
+use tomatobot::scheduler::retry_delay;
+
+#[test]
+fn retry_delay_doubles_up_to_the_max() {
+    let base = Duration::from_secs(30);
+    let max = Duration::from_secs(1800);
+    assert_eq!(retry_delay(base, max, 1), Duration::from_secs(30));
+    assert_eq!(retry_delay(base, max, 2), Duration::from_secs(60));
+    assert_eq!(retry_delay(base, max, 4), Duration::from_secs(240));
+    assert_eq!(retry_delay(base, max, 10), max);
+}
+
+#[test]
+fn retry_delay_saturates_instead_of_overflowing() {
+    let huge = Duration::from_secs(u64::MAX / 2);
+    assert_eq!(retry_delay(huge, Duration::MAX, u32::MAX), Duration::MAX);
+    assert_eq!(
+        retry_delay(huge, Duration::from_secs(1800), 1000),
+        Duration::from_secs(1800)
+    );
+}
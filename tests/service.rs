@@ -0,0 +1,603 @@
+//! The command flows end to end minus Discord: lookups through the service
+//! and the embeds the commands would send.
+
+mod common;
+
+use common::{example_emojis, json, MockUpstream, ACCOUNT_ID, CLAN_ID};
+use serde_json::Value;
+use std::time::{Duration, UNIX_EPOCH};
+use tomatobot::clans::generate_clan_embed;
+use tomatobot::player_stats::player::{generate_main_stat_embed, generate_period_embed, Period};
+use tomatobot::service::{PlayerLookup, TankCaches, TankFilter, TankLookup};
+use tomatobot::store::Dataset;
+use tomatobot::tanks::{
+    fetch_recent_tank_stats, fetch_tank_data, fetch_tank_economics, generate_mark_embed,
+};
+use tomatobot::vehicle::{Nation, TankClass};
+use tomatobot::Region;
+use wiremock::matchers::{method, path, query_param_is_missing};
+use wiremock::{Mock, ResponseTemplate};
+
+#[tokio::test]
+async fn stats_flow() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let service = upstream.service(TankCaches::default());
+
+    let (region, player) = service
+        .find_player("tomato_enjoyer", Some(Region::NA))
+        .await
+        .unwrap()
+        .found()
+        .unwrap();
+    let mut data = service.player_data(region, player).await;
+    assert!(data.overall.found().is_some());
+    assert!(data.recents.found().is_some());
+    assert!(data.player_clan.is_some());
+    assert!(data.clan.is_none());
+
+    service.refresh_player_data(&mut data).await;
+    assert!(data.is_in_clan);
+    assert_eq!(data.clan.as_ref().unwrap().tomato.tag, "RELIC");
+
+    let embed = generate_main_stat_embed(&data).await.unwrap();
+    assert_eq!(embed.0["title"], "tomato_enjoyer's Stats");
+    assert_eq!(
+        embed.0["url"],
+        format!("https://tomato.gg/stats/NA/tomato_enjoyer={}", ACCOUNT_ID)
+    );
+    assert_eq!(embed.0["description"], "**XO at [RELIC]**");
+
+    let embed = generate_period_embed(&data, Period::R3DAYS).await.unwrap();
+    let fields = embed.0["fields"].as_array().unwrap();
+    // totals plus both tanks, most played first
+    assert_eq!(fields.len(), 3);
+    assert_eq!(fields[1]["name"], "Obj. 140");
+}
+
+#[tokio::test]
+async fn stats_flow_without_clan() {
+    let upstream = MockUpstream::start().await;
+    // wiremock answers with the first mock mounted, so this beats mount_all
+    upstream
+        .mount(
+            "/na/wot/clans/accountinfo/",
+            json("clans_accountinfo_no_clan"),
+        )
+        .await;
+    upstream.mount_all().await;
+    let service = upstream.service(TankCaches::default());
+
+    let (region, player) = service
+        .find_player("tomato_enjoyer", Some(Region::NA))
+        .await
+        .unwrap()
+        .found()
+        .unwrap();
+    let mut data = service.player_data(region, player).await;
+    service.refresh_player_data(&mut data).await;
+
+    assert!(!data.is_in_clan);
+    assert!(data.clan.is_none());
+    let embed = generate_main_stat_embed(&data).await.unwrap();
+    assert!(!embed.0.contains_key("description"));
+}
+
+#[tokio::test]
+async fn stats_flow_clan_stats_down() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount(
+            &format!("/api/clan/com/{}", CLAN_ID),
+            ResponseTemplate::new(502),
+        )
+        .await;
+    upstream.mount_all().await;
+    let service = upstream.service(TankCaches::default());
+
+    let (region, player) = service
+        .find_player("tomato_enjoyer", Some(Region::NA))
+        .await
+        .unwrap()
+        .found()
+        .unwrap();
+    let mut data = service.player_data(region, player).await;
+    service.refresh_player_data(&mut data).await;
+
+    // still shown as a member, but without the Clan Stats button
+    assert!(data.player_clan.is_some());
+    assert!(data.clan.is_none());
+    assert!(!data.is_in_clan);
+}
+
+#[tokio::test]
+async fn stats_flow_not_on_tomato() {
+    let upstream = MockUpstream::start().await;
+    for route in [
+        format!("/api-v2/overall/com/{}", ACCOUNT_ID),
+        format!("/api-v2/recents/com/{}", ACCOUNT_ID),
+    ] {
+        upstream.mount(&route, json("overall_not_tracked")).await;
+    }
+    upstream.mount_all().await;
+    let service = upstream.service(TankCaches::default());
+
+    let (region, player) = service
+        .find_player("tomato_enjoyer", Some(Region::NA))
+        .await
+        .unwrap()
+        .found()
+        .unwrap();
+    let mut data = service.player_data(region, player).await;
+    service.refresh_player_data(&mut data).await;
+
+    assert!(generate_main_stat_embed(&data).await.is_err());
+    assert!(generate_period_embed(&data, Period::R24HR).await.is_err());
+    assert_eq!(
+        data.missing_stats_message(None).unwrap(),
+        "Player isn't tracked on Tomato.gg yet"
+    );
+}
+
+#[tokio::test]
+async fn stats_flow_schema_mismatch() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount(
+            &format!("/api-v2/overall/com/{}", ACCOUNT_ID),
+            json("overall_malformed"),
+        )
+        .await;
+    upstream.mount_all().await;
+    let service = upstream.service(TankCaches::default());
+
+    let (region, player) = service
+        .find_player("tomato_enjoyer", Some(Region::NA))
+        .await
+        .unwrap()
+        .found()
+        .unwrap();
+    let mut data = service.player_data(region, player).await;
+    service.refresh_player_data(&mut data).await;
+
+    // recents are fine, so periods still work
+    assert!(generate_main_stat_embed(&data).await.is_err());
+    assert!(generate_period_embed(&data, Period::R24HR).await.is_ok());
+    assert_eq!(
+        data.missing_stats_message(None).unwrap(),
+        "Tomato.gg changed how it sends stats, TomatoBot needs an update"
+    );
+    assert_eq!(data.missing_stats_message(Some(Period::R24HR)), None);
+}
+
+#[tokio::test]
+async fn refresh_keeps_cached_stats() {
+    let upstream = MockUpstream::start().await;
+    // only the uncached request fails, the cached one falls through to the
+    // fixture below
+    Mock::given(method("GET"))
+        .and(path(format!("/api-v2/overall/com/{}", ACCOUNT_ID)))
+        .and(query_param_is_missing("cache"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&upstream.server)
+        .await;
+    upstream.mount_all().await;
+    let service = upstream.service(TankCaches::default());
+
+    let (region, player) = service
+        .find_player("tomato_enjoyer", Some(Region::NA))
+        .await
+        .unwrap()
+        .found()
+        .unwrap();
+    let mut data = service.player_data(region, player).await;
+    service.refresh_player_data(&mut data).await;
+
+    assert_eq!(data.overall.found().unwrap().battles, 23814);
+}
+
+#[tokio::test]
+async fn player_stats_skips_the_clan_and_the_cache() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let service = upstream.service(TankCaches::default());
+
+    let (region, player) = service
+        .find_player("tomato_enjoyer", Some(Region::NA))
+        .await
+        .unwrap()
+        .found()
+        .unwrap();
+    let data = service.player_stats(region, player).await;
+
+    assert!(data.overall.found().is_some());
+    assert!(data.recents.found().is_some());
+    assert!(data.player_clan.is_none());
+    let requests = upstream.server.received_requests().await.unwrap();
+    assert!(requests.iter().all(|request| {
+        !request.url.path().contains("/wot/clans/") && request.url.query() != Some("cache=true")
+    }));
+}
+
+#[tokio::test]
+async fn player_stats_falls_back_to_the_cache() {
+    let upstream = MockUpstream::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/api-v2/overall/com/{}", ACCOUNT_ID)))
+        .and(query_param_is_missing("cache"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&upstream.server)
+        .await;
+    upstream.mount_all().await;
+    let service = upstream.service(TankCaches::default());
+
+    let (region, player) = service
+        .find_player("tomato_enjoyer", Some(Region::NA))
+        .await
+        .unwrap()
+        .found()
+        .unwrap();
+    let data = service.player_stats(region, player).await;
+
+    assert_eq!(data.overall.found().unwrap().battles, 23814);
+}
+
+#[tokio::test]
+async fn find_player_asks_when_several_regions_match() {
+    let upstream = MockUpstream::start().await;
+    for region in ["na", "eu"] {
+        upstream
+            .mount(
+                &format!("/{}/wot/account/list/", region),
+                json("account_list"),
+            )
+            .await;
+        upstream
+            .mount(
+                &format!("/{}/wot/account/info/", region),
+                json("account_info"),
+            )
+            .await;
+    }
+    upstream
+        .mount("/asia/wot/account/list/", json("account_list_empty"))
+        .await;
+    let service = upstream.service(TankCaches::default());
+
+    let PlayerLookup::Ambiguous { candidates, .. } =
+        service.find_player("tomato_enjoyer", None).await.unwrap()
+    else {
+        panic!("the name is on NA and EU");
+    };
+    assert_eq!(candidates.len(), 2);
+    assert_eq!(candidates[0].region, Region::NA);
+    assert_eq!(candidates[1].region, Region::EU);
+    assert_eq!(candidates[0].battles, Some(23814));
+    assert_eq!(
+        candidates[0].last_battle_time,
+        Some(UNIX_EPOCH + Duration::from_secs(1700000000))
+    );
+}
+
+#[tokio::test]
+async fn find_player_lists_candidates_without_account_info() {
+    let upstream = MockUpstream::start().await;
+    for region in ["na", "eu"] {
+        upstream
+            .mount(
+                &format!("/{}/wot/account/list/", region),
+                json("account_list"),
+            )
+            .await;
+        upstream
+            .mount(
+                &format!("/{}/wot/account/info/", region),
+                ResponseTemplate::new(500),
+            )
+            .await;
+    }
+    upstream
+        .mount("/asia/wot/account/list/", json("account_list_empty"))
+        .await;
+    let service = upstream.service(TankCaches::default());
+
+    let PlayerLookup::Ambiguous { candidates, .. } =
+        service.find_player("tomato_enjoyer", None).await.unwrap()
+    else {
+        panic!("the name is on NA and EU");
+    };
+    assert!(candidates
+        .iter()
+        .all(|c| c.battles.is_none() && c.last_battle_time.is_none()));
+}
+
+#[tokio::test]
+async fn find_player_asks_when_a_region_failed() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/na/wot/account/list/", json("wg_error_request_limit"))
+        .await;
+    upstream
+        .mount("/eu/wot/account/list/", json("account_list"))
+        .await;
+    upstream
+        .mount("/eu/wot/account/info/", json("account_info"))
+        .await;
+    upstream
+        .mount("/asia/wot/account/list/", json("account_list_empty"))
+        .await;
+    let service = upstream.service(TankCaches::default());
+
+    // the only match might not be the one that was meant
+    let PlayerLookup::Ambiguous {
+        candidates,
+        unchecked,
+    } = service.find_player("tomato_enjoyer", None).await.unwrap()
+    else {
+        panic!("NA couldn't be checked");
+    };
+    assert_eq!(candidates.len(), 1);
+    assert_eq!(candidates[0].region, Region::EU);
+    assert_eq!(unchecked, [Region::NA]);
+}
+
+#[tokio::test]
+async fn find_player_with_region_skips_the_others() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    upstream
+        .mount("/eu/wot/account/list/", json("account_list"))
+        .await;
+    let service = upstream.service(TankCaches::default());
+
+    let (region, _) = service
+        .find_player("tomato_enjoyer", Some(Region::EU))
+        .await
+        .unwrap()
+        .found()
+        .unwrap();
+    assert_eq!(region, Region::EU);
+}
+
+#[tokio::test]
+async fn marks_flow() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let tomato = upstream.tomato();
+
+    let caches = TankCaches::default();
+    let tanks = fetch_tank_data(&tomato, &Region::NA).await.unwrap();
+    let stats = fetch_recent_tank_stats(&tomato, &Region::NA).await.unwrap();
+    caches.tank_data.lock().await.insert(Region::NA, tanks);
+    caches
+        .recent_tank_stats
+        .lock()
+        .await
+        .insert(Region::NA, stats);
+    *caches.tank_economics.lock().await = fetch_tank_economics(&tomato).await.unwrap();
+    let service = upstream.service(caches);
+
+    let TankLookup::Found(marks) = service
+        .find_tank(Region::NA, "obj 140", &TankFilter::default())
+        .await
+    else {
+        panic!("tank data is loaded");
+    };
+    assert_eq!(marks.tank.id, 16897);
+
+    let embed = generate_mark_embed(
+        &marks.tank,
+        &Region::NA,
+        marks.economics.as_ref(),
+        marks.recent_stats.as_ref(),
+        &marks.ages,
+        &example_emojis(),
+    )
+    .await;
+    assert_eq!(embed.0["title"], "Obj. 140 NA");
+    assert_eq!(embed.0["url"], "https://tomato.gg/tanks/NA/16897");
+    let fields = embed.0["fields"].as_array().unwrap();
+    assert!(fields[0]["value"]
+        .as_str()
+        .unwrap()
+        .starts_with("100: `5439`"));
+    // the scheduler never ran, so there's no age to show
+    assert_eq!(embed.0["footer"]["text"], "Powered by Tomato.gg");
+}
+
+#[tokio::test]
+async fn marks_flow_filtered() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let caches = TankCaches::default();
+    let tanks = fetch_tank_data(&upstream.tomato(), &Region::NA)
+        .await
+        .unwrap();
+    caches.tank_data.lock().await.insert(Region::NA, tanks);
+    let service = upstream.service(caches);
+
+    // the closest name overall is filtered out, so the only light tank wins
+    let filter = TankFilter {
+        nation: None,
+        class: Some(TankClass::LT),
+    };
+    let TankLookup::Found(marks) = service.find_tank(Region::NA, "obj 140", &filter).await else {
+        panic!("a light tank is loaded");
+    };
+    assert_eq!(marks.tank.name, "Kunze Panzer");
+
+    let filter = TankFilter {
+        nation: Some(Nation::USSR),
+        class: Some(TankClass::LT),
+    };
+    assert!(matches!(
+        service.find_tank(Region::NA, "obj 140", &filter).await,
+        TankLookup::NoMatch
+    ));
+}
+
+#[tokio::test]
+async fn marks_flow_without_stats() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let caches = TankCaches::default();
+    let tanks = fetch_tank_data(&upstream.tomato(), &Region::NA)
+        .await
+        .unwrap();
+    caches.tank_data.lock().await.insert(Region::NA, tanks);
+    let service = upstream.service(caches);
+
+    let TankLookup::Found(marks) = service
+        .find_tank(Region::NA, "obj 140", &TankFilter::default())
+        .await
+    else {
+        panic!("tank data is loaded");
+    };
+    assert!(marks.economics.is_none() && marks.recent_stats.is_none());
+
+    let embed = generate_mark_embed(
+        &marks.tank,
+        &Region::NA,
+        marks.economics.as_ref(),
+        marks.recent_stats.as_ref(),
+        &marks.ages,
+        &example_emojis(),
+    )
+    .await;
+    let fields = embed.0["fields"].as_array().unwrap();
+    assert_eq!(fields[2]["value"], "Not enough data yet");
+    assert_eq!(fields[3]["value"], "Not enough data yet");
+}
+
+#[tokio::test]
+async fn marks_flow_not_loaded() {
+    let upstream = MockUpstream::start().await;
+    let service = upstream.service(TankCaches::default());
+
+    assert!(matches!(
+        service
+            .find_tank(Region::EU, "obj 140", &TankFilter::default())
+            .await,
+        TankLookup::NotLoaded { .. }
+    ));
+}
+
+#[tokio::test]
+async fn caches_missing_until_every_region_loads() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let tomato = upstream.tomato();
+
+    let caches = TankCaches::default();
+    assert_eq!(caches.missing().await.len(), 7);
+
+    // NA only, plus an empty EU list that can't answer /marks either
+    let tanks = fetch_tank_data(&tomato, &Region::NA).await.unwrap();
+    let stats = fetch_recent_tank_stats(&tomato, &Region::NA).await.unwrap();
+    caches
+        .tank_data
+        .lock()
+        .await
+        .insert(Region::NA, tanks.clone());
+    caches.tank_data.lock().await.insert(Region::EU, Vec::new());
+    caches
+        .recent_tank_stats
+        .lock()
+        .await
+        .insert(Region::NA, stats.clone());
+    *caches.tank_economics.lock().await = fetch_tank_economics(&tomato).await.unwrap();
+    assert_eq!(
+        caches.missing().await,
+        vec![
+            (Dataset::TankData, Some(Region::EU)),
+            (Dataset::RecentTankStats, Some(Region::EU)),
+            (Dataset::TankData, Some(Region::ASIA)),
+            (Dataset::RecentTankStats, Some(Region::ASIA)),
+        ]
+    );
+
+    for region in [Region::EU, Region::ASIA] {
+        caches.tank_data.lock().await.insert(region, tanks.clone());
+        caches
+            .recent_tank_stats
+            .lock()
+            .await
+            .insert(region, stats.clone());
+    }
+    assert!(caches.missing().await.is_empty());
+}
+
+#[tokio::test]
+async fn clanstats_flow() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let service = upstream.service(TankCaches::default());
+
+    let clan_id = service
+        .find_clan(Region::NA, "RELIC")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(clan_id, CLAN_ID);
+    let clan = service.clan_data(Region::NA, clan_id).await.unwrap();
+    let embed = generate_clan_embed(&clan).await;
+
+    assert_eq!(embed.0["title"], "[RELIC] Relentless");
+    assert_eq!(embed.0["color"], Value::from(0xb3261e));
+    let fields = embed.0["fields"].as_array().unwrap();
+    assert_eq!(
+        fields[3]["value"],
+        "Global Map ELO: `1187`\nGlobal Map WR: `65.0%`\nProvinces: `3`"
+    );
+}
+
+#[tokio::test]
+async fn clanstats_flow_upstream_down() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/na/wot/clans/list/", ResponseTemplate::new(500))
+        .await;
+    let service = upstream.service(TankCaches::default());
+
+    assert!(service.find_clan(Region::NA, "RELIC").await.is_err());
+}
+
+#[tokio::test]
+async fn search_players_reuses_recent_answers() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let service = upstream.service(TankCaches::default());
+
+    for prefix in ["tomato", "TOMATO"] {
+        let players = service.search_players(Region::NA, prefix).await.unwrap();
+        assert_eq!(players[0].nickname, "tomato_enjoyer");
+    }
+
+    // one request for both, case doesn't matter to WG
+    let requests = upstream.server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].url.query().unwrap().contains("limit=25"));
+}
+
+#[tokio::test]
+async fn search_players_skips_hopeless_prefixes() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+    let service = upstream.service(TankCaches::default());
+
+    for prefix in ["to", "tomato enjoyer", "tom&limit=1"] {
+        assert!(service
+            .search_players(Region::NA, prefix)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+    assert!(upstream
+        .server
+        .received_requests()
+        .await
+        .unwrap()
+        .is_empty());
+}
@@ -0,0 +1,103 @@
+use std::path::Path;
+use tomatobot::scheduler::SchedulerStatus;
+use tomatobot::service::TankCaches;
+use tomatobot::store::{Dataset, LinkedAccount, Store};
+use tomatobot::tanks::Tank;
+use tomatobot::Region;
+
+const DISCORD_ID: u64 = 187_654_321_098_765_432;
+
+fn account(region: Region, nickname: &str) -> LinkedAccount {
+    LinkedAccount {
+        region,
+        account_id: 1012345678,
+        nickname: nickname.to_owned(),
+    }
+}
+
+#[tokio::test]
+async fn link_and_look_up() {
+    let store = Store::open(Path::new(":memory:")).unwrap();
+    assert_eq!(store.linked_account(DISCORD_ID).await.unwrap(), None);
+
+    store
+        .link(DISCORD_ID, &account(Region::EU, "tomato_enjoyer"))
+        .await
+        .unwrap();
+    assert_eq!(
+        store.linked_account(DISCORD_ID).await.unwrap(),
+        Some(account(Region::EU, "tomato_enjoyer"))
+    );
+}
+
+#[tokio::test]
+async fn linking_again_replaces_the_account() {
+    let store = Store::open(Path::new(":memory:")).unwrap();
+    store
+        .link(DISCORD_ID, &account(Region::EU, "tomato_enjoyer"))
+        .await
+        .unwrap();
+    store
+        .link(DISCORD_ID, &account(Region::ASIA, "tomato_alt"))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        store.linked_account(DISCORD_ID).await.unwrap(),
+        Some(account(Region::ASIA, "tomato_alt"))
+    );
+}
+
+#[tokio::test]
+async fn unlink() {
+    let store = Store::open(Path::new(":memory:")).unwrap();
+    store
+        .link(DISCORD_ID, &account(Region::NA, "tomato_enjoyer"))
+        .await
+        .unwrap();
+
+    assert!(store.unlink(DISCORD_ID).await.unwrap());
+    assert_eq!(store.linked_account(DISCORD_ID).await.unwrap(), None);
+    // nothing left to remove
+    assert!(!store.unlink(DISCORD_ID).await.unwrap());
+}
+
+#[tokio::test]
+async fn datasets_are_saved_per_region() {
+    let store = Store::open(Path::new(":memory:")).unwrap();
+    store
+        .save(Dataset::RecentTankStats, Some(Region::NA), &vec![1, 2])
+        .await
+        .unwrap();
+    store
+        .save(Dataset::RecentTankStats, Some(Region::EU), &vec![3])
+        .await
+        .unwrap();
+
+    let load = |region| store.load::<Vec<u32>>(Dataset::RecentTankStats, region);
+    assert_eq!(load(Some(Region::NA)).await.unwrap().unwrap().value, [1, 2]);
+    assert_eq!(load(Some(Region::EU)).await.unwrap().unwrap().value, [3]);
+    assert!(load(Some(Region::ASIA)).await.unwrap().is_none());
+    assert!(load(None).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn only_saved_regions_get_an_age() {
+    let store = Store::open(Path::new(":memory:")).unwrap();
+    store
+        .save(Dataset::TankData, Some(Region::EU), &Vec::<Tank>::new())
+        .await
+        .unwrap();
+
+    let status = SchedulerStatus::default();
+    let caches = TankCaches::load(&store, &status).await;
+    assert!(caches.tank_data.lock().await.contains_key(&Region::EU));
+    assert!(status
+        .get(Dataset::TankData, Some(Region::EU))
+        .last_success
+        .is_some());
+    assert!(status
+        .get(Dataset::TankData, Some(Region::NA))
+        .last_success
+        .is_none());
+}
@@ -0,0 +1,262 @@
+mod common;
+
+use common::{fixture, json, MockUpstream};
+use serde_json::json;
+use tomatobot::errors::{Upstream, UpstreamErrorKind};
+use tomatobot::tanks::{
+    fetch_recent_tank_stats, fetch_tank_data, fetch_tank_economics, fuzzy_find_tank, Tank,
+};
+use tomatobot::Region;
+use wiremock::ResponseTemplate;
+
+fn tank_with_third(third: serde_json::Value) -> serde_json::Result<Tank> {
+    serde_json::from_value(json!({
+        "id": 1,
+        "nation": "usa",
+        "isPrem": false,
+        "class": "HT",
+        "name": "T110E5",
+        "tier": 10,
+        "3rd": third,
+    }))
+}
+
+#[test]
+fn third_class_number_is_kept() {
+    assert_eq!(tank_with_third(json!(1533)).unwrap().third, 1533);
+}
+
+#[test]
+fn third_class_string_means_missing() {
+    // Tomato sends "-" (or any string) when there isn't enough data
+    assert_eq!(tank_with_third(json!("-")).unwrap().third, 0);
+    assert_eq!(tank_with_third(json!("")).unwrap().third, 0);
+}
+
+#[test]
+fn third_class_other_types_are_rejected() {
+    assert!(tank_with_third(json!(true)).is_err());
+    assert!(tank_with_third(json!(-5)).is_err());
+    assert!(tank_with_third(json!(12.5)).is_err());
+}
+
+#[test]
+fn moe_fields_default_when_absent() {
+    let tank: Tank = serde_json::from_value(json!({
+        "id": 1,
+        "nation": "usa",
+        "isPrem": false,
+        "class": "HT",
+        "name": "T110E5",
+        "tier": 10,
+    }))
+    .unwrap();
+    assert_eq!(
+        (tank.pct_65, tank.pct_100, tank.third, tank.ace),
+        (0, 0, 0, 0)
+    );
+}
+
+#[test]
+fn fuzzy_find_tolerates_typos() {
+    let moe: serde_json::Value = serde_json::from_str(&fixture("moe")).unwrap();
+    let tanks: Vec<Tank> = serde_json::from_value(moe["data"].clone()).unwrap();
+    assert_eq!(fuzzy_find_tank("obj 140", &tanks), "Obj. 140");
+    assert_eq!(fuzzy_find_tank("kunze", &tanks), "Kunze Panzer");
+}
+
+#[tokio::test]
+async fn tank_data_merges_moe_and_mastery() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let tanks = fetch_tank_data(&upstream.tomato(), &Region::NA)
+        .await
+        .unwrap();
+    assert_eq!(tanks.len(), 2);
+
+    let obj = tanks.iter().find(|t| t.id == 16897).unwrap();
+    assert_eq!(obj.name, "Obj. 140");
+    assert_eq!(
+        (obj.pct_65, obj.pct_85, obj.pct_95, obj.pct_100),
+        (2957, 3647, 4280, 5439)
+    );
+    assert_eq!(
+        (obj.ace, obj.first, obj.second, obj.third),
+        (3588, 2871, 2112, 1533)
+    );
+
+    let kunze = tanks.iter().find(|t| t.id == 64817).unwrap();
+    assert!(kunze.is_prem);
+    assert_eq!(kunze.third, 0);
+}
+
+#[tokio::test]
+async fn tank_data_drops_tanks_missing_from_mastery() {
+    let upstream = MockUpstream::start().await;
+    let mut mastery: serde_json::Value = serde_json::from_str(&fixture("mastery")).unwrap();
+    mastery["data"]
+        .as_array_mut()
+        .unwrap()
+        .retain(|tank| tank["id"] == 16897);
+    upstream.mount("/api-v2/moe/com", json("moe")).await;
+    upstream
+        .mount(
+            "/api-v2/mastery/com",
+            ResponseTemplate::new(200).set_body_json(mastery),
+        )
+        .await;
+
+    let tanks = fetch_tank_data(&upstream.tomato(), &Region::NA)
+        .await
+        .unwrap();
+    assert_eq!(tanks.len(), 1);
+    assert_eq!(tanks[0].id, 16897);
+}
+
+#[tokio::test]
+async fn tank_data_with_nothing_left_is_an_error() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount("/api-v2/moe/com", json("moe")).await;
+    upstream
+        .mount(
+            "/api-v2/mastery/com",
+            ResponseTemplate::new(200).set_body_json(json!({
+                "meta": { "status": "ok" },
+                "data": [],
+            })),
+        )
+        .await;
+
+    // the scheduler keeps the last good copy on errors, so this can't be Ok
+    let error = fetch_tank_data(&upstream.tomato(), &Region::NA)
+        .await
+        .unwrap_err();
+    assert_eq!(error.kind, UpstreamErrorKind::Empty);
+    assert_eq!(error.region, Some(Region::NA));
+}
+
+#[tokio::test]
+async fn tank_data_status_not_ok_is_an_error() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount("/api-v2/moe/com", json("moe")).await;
+    upstream
+        .mount(
+            "/api-v2/mastery/com",
+            ResponseTemplate::new(200).set_body_json(json!({
+                "meta": { "status": "error" },
+                "data": [],
+            })),
+        )
+        .await;
+
+    let error = fetch_tank_data(&upstream.tomato(), &Region::NA)
+        .await
+        .unwrap_err();
+    assert_eq!(error.endpoint, "mastery");
+    assert_eq!(error.kind, UpstreamErrorKind::Unavailable);
+}
+
+#[tokio::test]
+async fn tank_data_server_error() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount("/api-v2/moe/com", ResponseTemplate::new(503))
+        .await;
+    upstream.mount("/api-v2/mastery/com", json("mastery")).await;
+
+    let error = fetch_tank_data(&upstream.tomato(), &Region::NA)
+        .await
+        .unwrap_err();
+    assert_eq!(error.upstream, Upstream::Tomato);
+    assert_eq!(error.endpoint, "moe");
+    assert_eq!(error.region, Some(Region::NA));
+    assert_eq!(error.status.map(|s| s.as_u16()), Some(503));
+    assert_eq!(error.kind, UpstreamErrorKind::Unavailable);
+    // first attempt plus one retry
+    let requests = upstream.server.received_requests().await.unwrap();
+    assert_eq!(
+        requests
+            .iter()
+            .filter(|r| r.url.path() == "/api-v2/moe/com")
+            .count(),
+        2
+    );
+}
+
+#[tokio::test]
+async fn many_retries_dont_overflow_the_backoff() {
+    let mut upstream = MockUpstream::start().await;
+    upstream.config.http.max_retries = 40;
+    upstream.config.http.retry_base_delay_ms = 0;
+    upstream
+        .mount("/api-v2/tank-economics", ResponseTemplate::new(503))
+        .await;
+
+    let error = fetch_tank_economics(&upstream.tomato()).await.unwrap_err();
+    assert_eq!(error.kind, UpstreamErrorKind::Unavailable);
+    let requests = upstream.server.received_requests().await.unwrap();
+    assert_eq!(requests.len(), 41);
+}
+
+#[tokio::test]
+async fn tank_data_malformed_payload() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount(
+            "/api-v2/moe/com",
+            ResponseTemplate::new(200).set_body_raw(
+                "{\"meta\": {\"status\": \"ok\"}, \"data\": [",
+                "application/json",
+            ),
+        )
+        .await;
+    upstream.mount("/api-v2/mastery/com", json("mastery")).await;
+
+    let error = fetch_tank_data(&upstream.tomato(), &Region::NA)
+        .await
+        .unwrap_err();
+    assert_eq!(error.kind, UpstreamErrorKind::Decode);
+    assert!(error
+        .to_string()
+        .starts_with("Tomato.gg moe (NA) decode error: "));
+}
+
+#[tokio::test]
+async fn tank_economics() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let economics = fetch_tank_economics(&upstream.tomato()).await.unwrap();
+    assert_eq!(economics.len(), 2);
+    let kunze = economics.iter().find(|e| e.id == 64817).unwrap();
+    assert_eq!(kunze.avg_profit, -1240);
+    assert_eq!(kunze.profit_per_minute, -181);
+}
+
+#[tokio::test]
+async fn recent_tank_stats() {
+    let upstream = MockUpstream::start().await;
+    upstream.mount_all().await;
+
+    let stats = fetch_recent_tank_stats(&upstream.tomato(), &Region::NA)
+        .await
+        .unwrap();
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[0].tank_id, 16897);
+}
+
+#[tokio::test]
+async fn recent_tank_stats_missing_field() {
+    let upstream = MockUpstream::start().await;
+    upstream
+        .mount(
+            "/api-v2/all-tanks-server-stats-wr-range/com/0/100",
+            ResponseTemplate::new(200).set_body_json(json!([{ "tank_id": 1, "name": "T110E5" }])),
+        )
+        .await;
+
+    assert!(fetch_recent_tank_stats(&upstream.tomato(), &Region::NA)
+        .await
+        .is_err());
+}
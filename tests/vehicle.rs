@@ -0,0 +1,93 @@
+use serde_json::json;
+use strum::IntoEnumIterator;
+use tomatobot::emoji::Emojis;
+use tomatobot::tanks::{create_tank_embed_description, Tank};
+use tomatobot::vehicle::{Nation, TankClass, Tier};
+
+#[test]
+fn known_values_parse() {
+    assert_eq!(
+        serde_json::from_value::<Nation>(json!("ussr")).unwrap(),
+        Nation::USSR
+    );
+    assert_eq!(
+        serde_json::from_value::<TankClass>(json!("SPG")).unwrap(),
+        TankClass::SPG
+    );
+}
+
+#[test]
+fn unknown_values_round_trip() {
+    let nation: Nation = serde_json::from_value(json!("merc")).unwrap();
+    assert_eq!(nation, Nation::Unknown(String::from("merc")));
+    assert_eq!(serde_json::to_value(&nation).unwrap(), json!("merc"));
+
+    let class: TankClass = serde_json::from_value(json!("wheeled")).unwrap();
+    assert_eq!(serde_json::to_value(&class).unwrap(), json!("wheeled"));
+}
+
+#[test]
+fn iteration_skips_unknown() {
+    assert_eq!(Nation::iter().count(), 11);
+    assert!(TankClass::iter().all(|class| !matches!(class, TankClass::Unknown(_))));
+    // every known variant serializes to something that parses back to it
+    for nation in Nation::iter() {
+        assert_eq!(Nation::from(nation.as_str()), nation);
+    }
+}
+
+#[test]
+fn choices_are_the_known_variants() {
+    let choices = <Nation as poise::SlashArgument>::choices();
+    assert_eq!(choices.len(), Nation::iter().count());
+    assert_eq!(choices[0].name, Nation::iter().next().unwrap().name());
+    let names: Vec<_> = <TankClass as poise::SlashArgument>::choices()
+        .into_iter()
+        .map(|choice| choice.name)
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "Light Tank",
+            "Medium Tank",
+            "Heavy Tank",
+            "Tank Destroyer",
+            "SPG"
+        ]
+    );
+
+    // discord sends back the index of the picked choice
+    for (index, class) in TankClass::iter().enumerate() {
+        assert_eq!(TankClass::from_choice(&json!(index)), Some(class));
+    }
+    assert_eq!(Nation::from_choice(&json!(99)), None);
+    assert_eq!(Nation::from_choice(&json!("usa")), None);
+}
+
+#[test]
+fn tiers_display_as_roman_numerals() {
+    assert_eq!(Tier(1).to_string(), "I");
+    assert_eq!(Tier(4).to_string(), "IV");
+    assert_eq!(Tier(9).to_string(), "IX");
+    assert_eq!(Tier(10).to_string(), "X");
+    // nothing sensible to show, fall back to the number
+    assert_eq!(Tier(0).to_string(), "0");
+    assert_eq!(Tier(12).to_string(), "12");
+}
+
+#[test]
+fn unknown_nation_and_class_show_their_name() {
+    let tank: Tank = serde_json::from_value(json!({
+        "id": 1,
+        "nation": "merc",
+        "isPrem": true,
+        "class": "wheeled",
+        "name": "Mystery",
+        "tier": 8,
+    }))
+    .unwrap();
+    assert_eq!(
+        create_tank_embed_description(&tank, &Emojis::default()),
+        "merc wheeled Tier VIII"
+    );
+}